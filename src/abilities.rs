@@ -0,0 +1,63 @@
+use crate::upgrades::{AbilityId, UpgradeCatalog};
+
+// Active gold multiplier from the Gold Rush ability
+#[derive(Clone, Copy, Debug)]
+pub struct GoldRush {
+    pub remaining: f32, // Seconds
+    pub multiplier: i32,
+}
+
+// Which abilities the player owns and how long until they can be used again
+pub struct Abilities {
+    owned: Vec<bool>,    // Indexed by AbilityId
+    cooldowns: Vec<f32>, // Indexed by AbilityId, seconds left
+    pub gold_rush: Option<GoldRush>,
+}
+
+impl Abilities {
+    pub fn new(catalog: &UpgradeCatalog) -> Self {
+        let count = catalog.abilities().count();
+        Self {
+            owned: vec![false; count],
+            cooldowns: vec![0.0; count],
+            gold_rush: None,
+        }
+    }
+
+    pub fn is_owned(&self, id: AbilityId) -> bool {
+        self.owned[id.0]
+    }
+
+    pub fn unlock(&mut self, id: AbilityId) {
+        self.owned[id.0] = true;
+    }
+
+    pub fn cooldown(&self, id: AbilityId) -> f32 {
+        self.cooldowns[id.0]
+    }
+
+    pub fn set_cooldown(&mut self, id: AbilityId, cooldown: f32) {
+        self.cooldowns[id.0] = cooldown;
+    }
+
+    pub fn is_ready(&self, id: AbilityId) -> bool {
+        self.is_owned(id) && self.cooldown(id) <= 0.0
+    }
+
+    pub fn update(&mut self, delta_time: f32) {
+        for cooldown in &mut self.cooldowns {
+            *cooldown = (*cooldown - delta_time).max(0.0);
+        }
+
+        if let Some(gold_rush) = &mut self.gold_rush {
+            gold_rush.remaining -= delta_time;
+            if gold_rush.remaining <= 0.0 {
+                self.gold_rush = None;
+            }
+        }
+    }
+
+    pub fn gold_multiplier(&self) -> i32 {
+        self.gold_rush.map_or(1, |gold_rush| gold_rush.multiplier)
+    }
+}
@@ -0,0 +1,112 @@
+use std::collections::BTreeSet;
+
+use serde::Deserialize;
+
+use crate::{block_registry::BlockRegistry, stats::Statistics, upgrades::UpgradeCatalog};
+
+#[derive(Clone, Debug, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum Condition {
+    // Without a block every block type counts
+    BlocksDestroyed { count: u64, block: Option<String> },
+    DepthReached { row: i64 },
+    GoldEarned { amount: u64 },
+    PickaxeBought { pickaxe: String },
+    EnemiesKilled { count: u64 },
+    TimePlayed { seconds: f32 },
+}
+
+#[derive(Clone, Debug, Deserialize)]
+pub struct AchievementDef {
+    pub id: String,
+    pub name: String,
+    pub description: String,
+    pub condition: Condition,
+}
+
+impl Condition {
+    fn is_met(&self, stats: &Statistics) -> bool {
+        match self {
+            Condition::BlocksDestroyed { count, block } => {
+                let destroyed = match block {
+                    Some(block) => stats.blocks.get(block).map_or(0, |block| block.destroyed),
+                    None => stats.blocks_destroyed(),
+                };
+                destroyed >= *count
+            }
+            Condition::DepthReached { row } => stats.deepest_row >= *row,
+            Condition::GoldEarned { amount } => stats.gold_earned >= *amount,
+            Condition::PickaxeBought { pickaxe } => stats.pickaxes_bought.contains(pickaxe),
+            Condition::EnemiesKilled { count } => stats.enemies_killed >= *count,
+            Condition::TimePlayed { seconds } => stats.seconds_played >= *seconds,
+        }
+    }
+}
+
+#[derive(Debug)]
+pub struct AchievementRegistry {
+    achievements: Vec<AchievementDef>,
+}
+
+impl AchievementRegistry {
+    // Blocks and pickaxes in the conditions are checked against the other definition files
+    pub fn from_json(
+        json: &str,
+        blocks: &BlockRegistry,
+        upgrades: &UpgradeCatalog,
+    ) -> Result<Self, String> {
+        let achievements: Vec<AchievementDef> =
+            serde_json::from_str(json).map_err(|err| err.to_string())?;
+
+        for (index, achievement) in achievements.iter().enumerate() {
+            if achievements[..index]
+                .iter()
+                .any(|other| other.id == achievement.id)
+            {
+                return Err(format!("duplicated achievement id '{}'", achievement.id));
+            }
+
+            match &achievement.condition {
+                Condition::BlocksDestroyed {
+                    block: Some(block), ..
+                } if blocks.find(block).is_none() => {
+                    return Err(format!(
+                        "achievement '{}' uses the unknown block '{}'",
+                        achievement.id, block
+                    ));
+                }
+                Condition::PickaxeBought { pickaxe }
+                    if upgrades.find_pickaxe(pickaxe).is_none() =>
+                {
+                    return Err(format!(
+                        "achievement '{}' uses the unknown pickaxe '{}'",
+                        achievement.id, pickaxe
+                    ));
+                }
+                _ => {}
+            }
+        }
+
+        Ok(Self { achievements })
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = &AchievementDef> {
+        self.achievements.iter()
+    }
+
+    // Adds every achievement whose condition is met to `unlocked` and returns the new ones
+    pub fn check(
+        &self,
+        stats: &Statistics,
+        unlocked: &mut BTreeSet<String>,
+    ) -> Vec<&AchievementDef> {
+        let mut newly_unlocked = Vec::new();
+        for achievement in &self.achievements {
+            if !unlocked.contains(&achievement.id) && achievement.condition.is_met(stats) {
+                unlocked.insert(achievement.id.clone());
+                newly_unlocked.push(achievement);
+            }
+        }
+        newly_unlocked
+    }
+}
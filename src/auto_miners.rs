@@ -0,0 +1,43 @@
+use crate::upgrades::{AutoMinerId, UpgradeCatalog};
+
+// How many helpers of each kind the player owns and how long until they hit again
+pub struct AutoMiners {
+    counts: Vec<u32>, // Indexed by AutoMinerId
+    timers: Vec<f32>, // Indexed by AutoMinerId, seconds since the last hit
+}
+
+impl AutoMiners {
+    pub fn new(catalog: &UpgradeCatalog) -> Self {
+        let count = catalog.auto_miners().count();
+        Self {
+            counts: vec![0; count],
+            timers: vec![0.0; count],
+        }
+    }
+
+    pub fn count(&self, id: AutoMinerId) -> u32 {
+        self.counts[id.0]
+    }
+
+    pub fn set_count(&mut self, id: AutoMinerId, count: u32) {
+        self.counts[id.0] = count;
+    }
+
+    pub fn total(&self) -> u32 {
+        self.counts.iter().sum()
+    }
+
+    // Returns true when the helpers of this kind should hit this frame
+    pub fn tick(&mut self, id: AutoMinerId, delta_time: f32, interval: f32) -> bool {
+        if self.counts[id.0] == 0 {
+            return false;
+        }
+
+        self.timers[id.0] += delta_time;
+        if self.timers[id.0] >= interval {
+            self.timers[id.0] -= interval;
+            return true;
+        }
+        false
+    }
+}
@@ -0,0 +1,128 @@
+use serde::Deserialize;
+
+use crate::block_registry::{BlockId, BlockRegistry};
+
+// Index of a biome inside the registry, biomes are sorted from the surface down
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct BiomeId(pub usize);
+
+#[derive(Deserialize)]
+struct BiomeFile {
+    id: String,
+    name: String,
+    min_row: i64,
+    fill: Option<String>,
+    strata: Option<String>,
+    ores: Option<Vec<String>>,
+    #[serde(default = "default_cave_multiplier")]
+    cave_multiplier: f32,
+    background_tint: [f32; 3],
+    music: String,
+}
+
+fn default_cave_multiplier() -> f32 {
+    1.0
+}
+
+#[derive(Debug)]
+pub struct Biome {
+    pub id: String,
+    pub name: String,
+    // First row (counting from the surface) that belongs to this biome
+    pub min_row: i64,
+    // Block used to fill the space left by ores and caves
+    pub fill: BlockId,
+    pub strata: Option<BlockId>,
+    // Blocks with a spawn curve that can spawn here, None means all of them
+    pub ores: Option<Vec<BlockId>>,
+    pub cave_multiplier: f32,
+    pub background_tint: [f32; 3],
+    pub music: String,
+}
+
+impl Biome {
+    pub fn allows_block(&self, id: BlockId) -> bool {
+        self.ores.as_ref().is_none_or(|ores| ores.contains(&id))
+    }
+}
+
+#[derive(Debug)]
+pub struct BiomeRegistry {
+    biomes: Vec<Biome>,
+}
+
+impl BiomeRegistry {
+    // Block ids are resolved against the block registry, missing fill/strata blocks use the registry defaults
+    pub fn from_json(json: &str, blocks: &BlockRegistry) -> Result<Self, String> {
+        let files: Vec<BiomeFile> = serde_json::from_str(json).map_err(|err| err.to_string())?;
+
+        let find_block = |biome: &str, id: &str| {
+            blocks
+                .find(id)
+                .ok_or_else(|| format!("biome '{}' uses the unknown block '{}'", biome, id))
+        };
+
+        let mut biomes = Vec::new();
+        for file in files {
+            let fill = match &file.fill {
+                Some(fill) => find_block(&file.id, fill)?,
+                None => blocks.fill_block(),
+            };
+            let strata = match &file.strata {
+                Some(strata) => Some(find_block(&file.id, strata)?),
+                None => blocks.strata_block(),
+            };
+            let ores = match &file.ores {
+                Some(ores) => Some(
+                    ores.iter()
+                        .map(|ore| find_block(&file.id, ore))
+                        .collect::<Result<Vec<_>, _>>()?,
+                ),
+                None => None,
+            };
+
+            biomes.push(Biome {
+                id: file.id,
+                name: file.name,
+                min_row: file.min_row,
+                fill,
+                strata,
+                ores,
+                cave_multiplier: file.cave_multiplier,
+                background_tint: file.background_tint,
+                music: file.music,
+            });
+        }
+
+        biomes.sort_by_key(|biome| biome.min_row);
+        match biomes.first() {
+            Some(first) if first.min_row <= 0 => Ok(Self { biomes }),
+            Some(first) => Err(format!(
+                "the first biome ('{}') has to start at row 0",
+                first.id
+            )),
+            None => Err(String::from("at least one biome is needed")),
+        }
+    }
+
+    pub fn get(&self, id: BiomeId) -> &Biome {
+        &self.biomes[id.0]
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = (BiomeId, &Biome)> {
+        self.biomes
+            .iter()
+            .enumerate()
+            .map(|(index, biome)| (BiomeId(index), biome))
+    }
+
+    // The deepest biome that starts at or above the row
+    pub fn biome_at(&self, row: i64) -> BiomeId {
+        let index = self
+            .biomes
+            .iter()
+            .rposition(|biome| biome.min_row <= row)
+            .unwrap_or(0);
+        BiomeId(index)
+    }
+}
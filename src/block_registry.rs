@@ -0,0 +1,134 @@
+use serde::Deserialize;
+
+// Index of a block definition inside the registry
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct BlockId(pub usize);
+
+// How likely a block is to spawn at a given world y (in pixels, same as the block shapes).
+// probability = min(log_factor * ln(y - min_depth), min(base_cap + cap_growth * max(y - cap_growth_start, 0), max_cap))
+#[derive(Clone, Debug, Deserialize)]
+pub struct SpawnCurve {
+    pub min_depth: f32,
+    pub log_factor: f32,
+    pub base_cap: f32,
+    pub cap_growth: f32,
+    pub cap_growth_start: f32,
+    pub max_cap: f32,
+    // Size of the veins, smaller values make bigger clusters
+    #[serde(default = "default_vein_scale")]
+    pub vein_scale: f32,
+}
+
+fn default_vein_scale() -> f32 {
+    0.3
+}
+
+impl SpawnCurve {
+    pub fn probability(&self, y: f32) -> f32 {
+        if y <= self.min_depth {
+            return 0.0;
+        }
+
+        let dynamic_cap = (self.base_cap + self.cap_growth * (y - self.cap_growth_start).max(0.0))
+            .min(self.max_cap);
+        (self.log_factor * (y - self.min_depth).ln()).min(dynamic_cap)
+    }
+}
+
+#[derive(Clone, Debug, Deserialize)]
+pub struct BlockDef {
+    pub id: String,
+    pub texture: String,
+    pub base_hp: i32,
+    pub score: i32,
+    pub gold: i32,
+    // Pickaxes with a lower tier than this deal reduced damage to the block
+    pub hardness: u32,
+    // Blocks without a spawn curve are used to fill the space left by the others
+    pub spawn: Option<SpawnCurve>,
+    // Ores keep spawning inside the hard strata bands, everything else gets replaced by the strata block
+    #[serde(default)]
+    pub ore: bool,
+    // The block used to build the hard strata bands
+    #[serde(default)]
+    pub strata: bool,
+    // Color multiplied with the texture, so a texture can be reused by different blocks
+    #[serde(default)]
+    pub tint: Option<[f32; 3]>,
+    // Item added to the backpack when the block breaks, these blocks pay through the item's price instead of `gold`
+    #[serde(default)]
+    pub drop: Option<String>,
+}
+
+#[derive(Debug)]
+pub struct BlockRegistry {
+    blocks: Vec<BlockDef>,
+    fill_block: BlockId,
+    strata_block: Option<BlockId>,
+}
+
+impl BlockRegistry {
+    pub fn from_json(json: &str) -> Result<Self, String> {
+        let blocks: Vec<BlockDef> = serde_json::from_str(json).map_err(|err| err.to_string())?;
+
+        for (index, block) in blocks.iter().enumerate() {
+            if blocks[..index].iter().any(|other| other.id == block.id) {
+                return Err(format!("duplicated block id '{}'", block.id));
+            }
+            if block.base_hp <= 0 {
+                return Err(format!("block '{}' needs a positive base_hp", block.id));
+            }
+            if block.drop.is_some() && block.gold != 0 {
+                return Err(format!(
+                    "block '{}' drops an item, so it can't pay gold as well",
+                    block.id
+                ));
+            }
+            if block.strata && block.spawn.is_some() {
+                return Err(format!(
+                    "strata block '{}' can't have a spawn curve",
+                    block.id
+                ));
+            }
+        }
+
+        let fill_block = blocks
+            .iter()
+            .position(|block| block.spawn.is_none() && !block.strata)
+            .map(BlockId)
+            .ok_or_else(|| String::from("at least one block needs to have no spawn curve"))?;
+        let strata_block = blocks.iter().position(|block| block.strata).map(BlockId);
+
+        Ok(Self {
+            blocks,
+            fill_block,
+            strata_block,
+        })
+    }
+
+    pub fn get(&self, id: BlockId) -> &BlockDef {
+        &self.blocks[id.0]
+    }
+
+    pub fn find(&self, id: &str) -> Option<BlockId> {
+        self.blocks
+            .iter()
+            .position(|block| block.id == id)
+            .map(BlockId)
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = (BlockId, &BlockDef)> {
+        self.blocks
+            .iter()
+            .enumerate()
+            .map(|(index, block)| (BlockId(index), block))
+    }
+
+    pub fn fill_block(&self) -> BlockId {
+        self.fill_block
+    }
+
+    pub fn strata_block(&self) -> Option<BlockId> {
+        self.strata_block
+    }
+}
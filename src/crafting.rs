@@ -0,0 +1,54 @@
+use std::collections::VecDeque;
+
+use crate::recipes::{RecipeBook, RecipeId};
+
+pub const MAX_QUEUE: usize = 5;
+
+#[derive(Clone, Copy, Debug)]
+pub struct CraftJob {
+    pub recipe: RecipeId,
+    pub elapsed: f32, // Seconds, only the first job in the queue moves forward
+}
+
+// Recipes waiting to be made, one at a time in the order they were queued. The ingredients are taken
+// from the backpack when a recipe is queued
+#[derive(Default)]
+pub struct Crafting {
+    queue: VecDeque<CraftJob>,
+}
+
+impl Crafting {
+    pub fn new() -> Self {
+        Self {
+            queue: VecDeque::new(),
+        }
+    }
+
+    pub fn jobs(&self) -> impl Iterator<Item = &CraftJob> {
+        self.queue.iter()
+    }
+
+    pub fn is_full(&self) -> bool {
+        self.queue.len() >= MAX_QUEUE
+    }
+
+    pub fn is_queued(&self, recipe: RecipeId) -> bool {
+        self.queue.iter().any(|job| job.recipe == recipe)
+    }
+
+    pub fn push(&mut self, recipe: RecipeId, elapsed: f32) {
+        self.queue.push_back(CraftJob { recipe, elapsed });
+    }
+
+    // Returns the first recipe once it's done, it stays in the queue until `finish` is called
+    pub fn update(&mut self, delta_time: f32, recipes: &RecipeBook) -> Option<RecipeId> {
+        let job = self.queue.front_mut()?;
+        let time = recipes.get(job.recipe).time;
+        job.elapsed = (job.elapsed + delta_time).min(time);
+        (job.elapsed >= time).then_some(job.recipe)
+    }
+
+    pub fn finish(&mut self) {
+        self.queue.pop_front();
+    }
+}
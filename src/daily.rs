@@ -0,0 +1,82 @@
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    rng::{self, Rng},
+    save, stats,
+};
+
+pub const ROCK_HEAVY_STRATA: f32 = 2.5; // Multiplier for how much of the world is strata
+pub const DOUBLE_GOLD: f32 = 2.0;
+
+// Twist of the day, every player gets the same one
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum DailyModifier {
+    NoShop,
+    RockHeavy,
+    DoubleGold,
+}
+
+impl DailyModifier {
+    const ALL: [DailyModifier; 3] = [
+        DailyModifier::NoShop,
+        DailyModifier::RockHeavy,
+        DailyModifier::DoubleGold,
+    ];
+
+    pub fn for_day(day: i64) -> Self {
+        let roll = Rng::new(seed(day)).next_u64();
+        Self::ALL[(roll % Self::ALL.len() as u64) as usize]
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            DailyModifier::NoShop => "No Shop",
+            DailyModifier::RockHeavy => "Rock Heavy",
+            DailyModifier::DoubleGold => "Double Gold",
+        }
+    }
+}
+
+// Best run of a day, kept in the profile
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct DailyResult {
+    pub day: i64, // Days since 1970-01-01
+    pub modifier: DailyModifier,
+    pub score: i32,
+    pub gold: i32,
+    pub deepest_row: i64,
+    pub time: f32, // Seconds
+    pub attempts: u32,
+}
+
+impl DailyResult {
+    // Plain text summary to paste in a chat
+    pub fn share_text(&self) -> String {
+        format!(
+            "Dwarfing Daily Dig {} ({})\nScore {} | Row {} | {} gold | {}",
+            format_day(self.day),
+            self.modifier.name(),
+            self.score,
+            self.deepest_row,
+            self.gold,
+            stats::format_duration(self.time)
+        )
+    }
+}
+
+// Current UTC day, the daily challenge changes at midnight UTC for everyone
+pub fn today() -> i64 {
+    (save::unix_time() / 86_400) as i64
+}
+
+// "YYYY-MM-DD"
+pub fn format_day(day: i64) -> String {
+    let (year, month, day) = stats::civil_from_days(day);
+    format!("{:04}-{:02}-{:02}", year, month, day)
+}
+
+// Same as typing the date as the world seed, so the daily world can be replayed later from the menu
+pub fn seed(day: i64) -> u64 {
+    rng::seed_from_text(&format_day(day)).expect("The date is never empty")
+}
@@ -0,0 +1,111 @@
+use macroquad::file::load_string;
+
+use crate::{
+    achievements::AchievementRegistry, biome::BiomeRegistry, block_registry::BlockRegistry,
+    enemy_registry::EnemyRegistry, items::ItemRegistry, recipes::RecipeBook,
+    upgrades::UpgradeCatalog,
+};
+
+// All the data-driven definitions that live in the assets folder, shared by the simulation and the frontend
+#[derive(Debug)]
+pub struct Definitions {
+    pub blocks: BlockRegistry,
+    pub items: ItemRegistry,
+    pub upgrades: UpgradeCatalog,
+    pub recipes: RecipeBook,
+    pub biomes: BiomeRegistry,
+    pub enemies: EnemyRegistry,
+    pub achievements: AchievementRegistry,
+}
+
+impl Definitions {
+    pub async fn load() -> Self {
+        let blocks = load_string("blocks.json")
+            .await
+            .expect("Can't load block definitions");
+        let blocks = BlockRegistry::from_json(&blocks)
+            .unwrap_or_else(|err| panic!("Can't parse block definitions: {}", err));
+
+        let items = load_string("items.json")
+            .await
+            .expect("Can't load item definitions");
+        let items = ItemRegistry::from_json(&items, &blocks)
+            .unwrap_or_else(|err| panic!("Can't parse item definitions: {}", err));
+
+        let upgrades = load_string("upgrades.json")
+            .await
+            .expect("Can't load upgrade catalog");
+        let upgrades = UpgradeCatalog::from_json(&upgrades)
+            .unwrap_or_else(|err| panic!("Can't parse upgrade catalog: {}", err));
+
+        let recipes = load_string("recipes.json")
+            .await
+            .expect("Can't load recipes");
+        let recipes = RecipeBook::from_json(&recipes, &blocks, &items, &upgrades)
+            .unwrap_or_else(|err| panic!("Can't parse recipes: {}", err));
+
+        let biomes = load_string("biomes.json")
+            .await
+            .expect("Can't load biome definitions");
+        let biomes = BiomeRegistry::from_json(&biomes, &blocks)
+            .unwrap_or_else(|err| panic!("Can't parse biome definitions: {}", err));
+
+        let enemies = load_string("enemies.json")
+            .await
+            .expect("Can't load enemy definitions");
+        let enemies = EnemyRegistry::from_json(&enemies)
+            .unwrap_or_else(|err| panic!("Can't parse enemy definitions: {}", err));
+
+        let achievements = load_string("achievements.json")
+            .await
+            .expect("Can't load achievement definitions");
+        let achievements = AchievementRegistry::from_json(&achievements, &blocks, &upgrades)
+            .unwrap_or_else(|err| panic!("Can't parse achievement definitions: {}", err));
+
+        Self {
+            blocks,
+            items,
+            upgrades,
+            recipes,
+            biomes,
+            enemies,
+            achievements,
+        }
+    }
+}
+
+#[cfg(test)]
+impl Definitions {
+    // The definitions from the assets folder without a window to load them through
+    pub fn shipped() -> Self {
+        let blocks = BlockRegistry::from_json(include_str!("../assets/blocks.json")).unwrap();
+        let items = ItemRegistry::from_json(include_str!("../assets/items.json"), &blocks).unwrap();
+        let upgrades = UpgradeCatalog::from_json(include_str!("../assets/upgrades.json")).unwrap();
+        let recipes = RecipeBook::from_json(
+            include_str!("../assets/recipes.json"),
+            &blocks,
+            &items,
+            &upgrades,
+        )
+        .unwrap();
+        let biomes =
+            BiomeRegistry::from_json(include_str!("../assets/biomes.json"), &blocks).unwrap();
+        let enemies = EnemyRegistry::from_json(include_str!("../assets/enemies.json")).unwrap();
+        let achievements = AchievementRegistry::from_json(
+            include_str!("../assets/achievements.json"),
+            &blocks,
+            &upgrades,
+        )
+        .unwrap();
+
+        Self {
+            blocks,
+            items,
+            upgrades,
+            recipes,
+            biomes,
+            enemies,
+            achievements,
+        }
+    }
+}
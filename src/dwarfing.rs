@@ -1,18 +1,48 @@
-use macroquad::rand::rand;
 use macroquad::ui::{hash, root_ui};
 use macroquad::{audio::stop_sound, prelude::*};
 
-use crate::player::Pickaxe;
 use crate::{
-    block::{Block, BlockType},
-    player::Player,
+    biome::BiomeId,
+    crafting::MAX_QUEUE,
+    daily::{DailyModifier, DailyResult},
+    highscores::HighScoreEntry,
+    modes::{GameMode, ModeRules},
+    player::{Facing, Player, PlayerSprite, MAX_HEALTH},
+    profile::{self, Profile},
+    replay::{self, Replay, ReplayFrame},
     resources::Resources,
+    save::{self, SaveError, SaveGame},
     score::Score,
     shape::Shape,
+    simulation::{
+        Command, OfflineReport, SimEvent, SimInput, Simulation, SoundEffect, BLOCK_SIZE,
+        WORN_DURABILITY,
+    },
+    stats::RunRecord,
+    world::World,
 };
 
-const GRAVITY: f32 = 800.0;
-const BLOCK_SIZE: f32 = 32.0;
+const AUTOSAVE_INTERVAL: f32 = 30.0; // Seconds
+const BIOME_BANNER_DURATION: f32 = 3.0; // Seconds
+const TOAST_DURATION: f32 = 4.0; // Seconds
+
+// Abilities get their hotkey from their position in upgrades.json
+const ABILITY_KEYS: [KeyCode; 9] = [
+    KeyCode::Key1,
+    KeyCode::Key2,
+    KeyCode::Key3,
+    KeyCode::Key4,
+    KeyCode::Key5,
+    KeyCode::Key6,
+    KeyCode::Key7,
+    KeyCode::Key8,
+    KeyCode::Key9,
+];
+const PLAYBACK_SPEEDS: [usize; 4] = [1, 4, 16, 64]; // Recorded ticks per tick
+const TICK_RATE: f32 = 60.0; // Simulation ticks per second, no matter the frame rate
+const TICK: f32 = 1.0 / TICK_RATE;
+const LOOK_STEP: f32 = BLOCK_SIZE * 2.0; // How far up the camera moves per mouse wheel step
+const MAX_FRAME_TIME: f32 = 0.25; // Longer hitches slow the game down instead of running a pile of ticks at once
 
 #[derive(Debug, PartialEq)]
 enum DebugMode {
@@ -20,84 +50,310 @@ enum DebugMode {
     Disabled,
 }
 
+// Only runs started from the menu are recorded, a replay has to start at the very first frame
+enum ReplayState {
+    Off,
+    Recording(Replay),
+    Playback {
+        replay: Replay,
+        frame: usize,
+        speed: usize, // Index in PLAYBACK_SPEEDS
+    },
+}
+
 #[derive(Debug, PartialEq)]
 enum PlayingThemeAudio {
     OpeningTheme,
-    GameTheme,
-}
-
-struct Params {
-    needed_x: i32,
-    block_area_top: f32,
-    last_row_y: f32,
+    Biome(BiomeId),
 }
 
+// Macroquad frontend, feeds input to the simulation and renders the result
 pub struct Dwarfing {
     debug_mode: DebugMode,
-    score: Score,
     playing_theme: PlayingThemeAudio,
     resources: Resources,
-    player: Player,
-    blocks: Vec<Block>,
-    params: Params,
+    simulation: Simulation,
+    player_sprite: PlayerSprite,
+    pending_commands: Vec<Command>,
     is_shop_open: bool,
+    is_crafting_open: bool,
+    look_offset: f32, // How far above the player the camera is, scrolled with the mouse wheel
+    autosave_timer: f32,
+    // Biome whose name is shown after crossing into it, and for how long it stays on screen
+    biome_banner: Option<(BiomeId, f32)>,
+    // Shown until the player closes it, right after continuing a saved game
+    offline_report: Option<OfflineReport>,
+    is_prestige_open: bool,
+    is_retire_open: bool,
+    // Runes earned by resetting the world, the run is over once this is set
+    claimed_prestige: Option<u32>,
+    // Owned by the game while playing so achievements can be saved as they unlock, main gets it back afterwards
+    profile: Profile,
+    // Achievement unlock messages and how long they stay on screen
+    toasts: Vec<(String, f32)>,
+    mode: GameMode,
+    rules: Box<dyn ModeRules>,
+    replay: ReplayState,
+    // Frame time not simulated yet, less than a tick once the update is done
+    tick_accumulator: f32,
+    // Input gathered since the last tick, so a click on a frame without a tick isn't lost
+    input: SimInput,
 }
 
 impl Dwarfing {
-    pub fn init(resources: Resources) -> Self {
-        let player_shape = Shape {
-            x: screen_width() / 2.0,
-            y: 0.0,
-            size: Vec2::splat(32.0),
-            color: BLUE,
+    pub fn init(resources: Resources, seed: u64, profile: Profile, mode: GameMode) -> Self {
+        let mut simulation = Simulation::new(
+            vec2(screen_width(), screen_height()),
+            seed,
+            resources.definitions.clone(),
+        );
+        simulation.bonuses = profile.bonuses(&resources.definitions.upgrades);
+        simulation.started_at = save::unix_time();
+
+        let replay = Replay::new(
+            seed,
+            mode,
+            simulation.bonuses,
+            vec2(screen_width(), screen_height()),
+        );
+        let mut dwarfing = Self::with_simulation(resources, simulation, profile, mode);
+        dwarfing.replay = ReplayState::Recording(replay);
+        dwarfing
+    }
+
+    // Nothing is saved while watching a replay, so any profile does
+    pub fn from_replay(resources: Resources, replay: Replay) -> Self {
+        let simulation = replay.simulation(resources.definitions.clone());
+        let mode = replay.mode;
+        let mut dwarfing = Self::with_simulation(resources, simulation, Profile::default(), mode);
+        dwarfing.replay = ReplayState::Playback {
+            replay,
+            frame: 0,
+            speed: 0,
         };
+        dwarfing
+    }
+
+    pub fn from_save(
+        resources: Resources,
+        save: &SaveGame,
+        profile: Profile,
+    ) -> Result<Self, SaveError> {
+        let mut simulation = Simulation::from_save(
+            save,
+            vec2(screen_width(), screen_height()),
+            resources.definitions.clone(),
+        )?;
+        simulation.bonuses = profile.bonuses(&resources.definitions.upgrades);
 
-        let score = Score::init();
+        let rules = save.mode.rules();
+        let mut offline_events = Vec::new();
+        let offline_report = if save.saved_at > 0 && rules.has_offline_progress() {
+            let elapsed = save::unix_time().saturating_sub(save.saved_at);
+            simulation.apply_offline_progress(elapsed as f32, &mut offline_events)
+        } else {
+            None
+        };
 
-        let player = Player::new(player_shape, resources.player_texture_basic.clone());
-        let blocks = Vec::new();
+        let mut dwarfing = Self::with_simulation(resources, simulation, profile, save.mode);
+        dwarfing.offline_report = offline_report;
+        // The offline gold counts for the stats and achievements like any other
+        dwarfing.handle_events(offline_events);
+        Ok(dwarfing)
+    }
 
-        let needed_x = (screen_width() / BLOCK_SIZE).ceil() as i32;
-        let block_area_top = screen_height() / 2.0;
-        let last_row_y = block_area_top;
+    fn with_simulation(
+        resources: Resources,
+        mut simulation: Simulation,
+        profile: Profile,
+        mode: GameMode,
+    ) -> Self {
+        let rules = mode.rules();
+        rules.setup(&mut simulation);
+
+        let player_sprite = PlayerSprite::new(
+            resources
+                .player_texture(simulation.effective_pickaxe())
+                .clone(),
+        );
 
         let playing_theme = PlayingThemeAudio::OpeningTheme;
 
         Self {
             debug_mode: DebugMode::Disabled,
-            score,
             playing_theme,
             resources,
-            player,
-            blocks,
-            params: Params {
-                needed_x,
-                block_area_top,
-                last_row_y,
-            },
+            simulation,
+            player_sprite,
+            pending_commands: Vec::new(),
             is_shop_open: false,
+            is_crafting_open: false,
+            look_offset: 0.0,
+            autosave_timer: 0.0,
+            biome_banner: None,
+            offline_report: None,
+            is_prestige_open: false,
+            is_retire_open: false,
+            claimed_prestige: None,
+            profile,
+            toasts: Vec::new(),
+            mode,
+            rules,
+            replay: ReplayState::Off,
+            tick_accumulator: 0.0,
+            input: SimInput::default(),
+        }
+    }
+
+    pub fn save(&self) -> Result<(), SaveError> {
+        let mut save = self.simulation.to_save();
+        save.saved_at = save::unix_time();
+        save.mode = self.mode;
+        save::write(&save)?;
+        profile::write(&self.profile)
+    }
+
+    // Only on game over and quit, the frames keep growing so it's too big for every autosave
+    pub fn write_replay(&self) -> Result<(), SaveError> {
+        match &self.replay {
+            ReplayState::Recording(replay) => replay::write(replay),
+            _ => Ok(()),
+        }
+    }
+
+    // Once every frame of the replay ran, whether it ended up in the recorded state
+    pub fn playback_result(&self) -> Option<Result<(), String>> {
+        let ReplayState::Playback { replay, frame, .. } = &self.replay else {
+            return None;
+        };
+        if *frame < replay.frames.len() {
+            return None;
+        }
+
+        Some(match replay.final_hash {
+            Some(hash) if hash == self.simulation.state_hash() => Ok(()),
+            Some(_) => Err(String::from(
+                "the final state differs from the recorded one",
+            )),
+            None => Err(String::from("the replay has no final state to compare")),
+        })
+    }
+
+    pub fn is_game_over(&self) -> bool {
+        self.simulation.is_game_over() || self.rules.is_finished(&self.simulation)
+    }
+
+    pub fn mode(&self) -> GameMode {
+        self.mode
+    }
+
+    pub fn counts_for_high_scores(&self) -> bool {
+        self.rules.counts_for_high_scores(&self.simulation)
+    }
+
+    pub fn score(&self) -> &Score {
+        &self.simulation.score
+    }
+
+    pub fn profile(&self) -> &Profile {
+        &self.profile
+    }
+
+    pub fn claimed_prestige(&self) -> Option<u32> {
+        self.claimed_prestige
+    }
+
+    // Summary of the run for the statistics, called once it is over
+    pub fn run_record(&self) -> RunRecord {
+        RunRecord {
+            started_at: self.simulation.started_at,
+            duration: self.simulation.run_time,
+            score: self.simulation.score.current_score,
+            gold: self.simulation.score.gold,
+            blocks_destroyed: self.simulation.score.blocks_destroyed,
+            deepest_row: self.simulation.deepest_row,
+        }
+    }
+
+    // None unless this run is a daily challenge
+    pub fn daily_result(&self) -> Option<DailyResult> {
+        let GameMode::Daily(day) = self.mode else {
+            return None;
+        };
+        Some(DailyResult {
+            day,
+            modifier: DailyModifier::for_day(day),
+            score: self.simulation.score.current_score,
+            gold: self.simulation.score.gold,
+            deepest_row: self.simulation.deepest_row,
+            time: self.simulation.run_time,
+            attempts: 1,
+        })
+    }
+
+    // The initials are filled in by the game over screen
+    pub fn high_score_entry(&self) -> HighScoreEntry {
+        HighScoreEntry {
+            initials: String::new(),
+            score: self.simulation.score.current_score,
+            gold: self.simulation.score.gold,
+            deepest_row: self.simulation.deepest_row,
+            time: self.simulation.run_time,
+            date: save::unix_time(),
         }
     }
 
     pub fn update(&mut self) {
         self.init_music();
-        self.apply_gravity();
-        self.update_player_position();
-        self.update_blocks();
-        self.remove_off_screen_blocks();
-        self.player_collision();
-        self.handle_input();
+
+        if is_key_pressed(KeyCode::F1) {
+            self.debug_mode = match self.debug_mode {
+                DebugMode::Enabled => DebugMode::Disabled,
+                DebugMode::Disabled => DebugMode::Enabled,
+            };
+        }
+
+        self.tick_accumulator += get_frame_time().min(MAX_FRAME_TIME);
+        let mut ticks = 0;
+        while self.tick_accumulator >= TICK {
+            self.tick_accumulator -= TICK;
+            ticks += 1;
+        }
+
+        if matches!(self.replay, ReplayState::Playback { .. }) {
+            self.update_playback(ticks);
+        } else {
+            self.collect_input();
+            for _ in 0..ticks {
+                self.tick();
+            }
+
+            self.update_achievements();
+            self.handle_autosave();
+        }
+
+        self.update_biome_banner();
+        self.update_sprite();
         self.handle_camera();
     }
 
     pub fn draw(&mut self) {
         self.draw_background(); // TODO: tbh I should not make the background like this.
+        self.draw_base();
         self.draw_blocks();
+        self.draw_ladders();
+        self.draw_enemies();
+        self.draw_mining_target();
         self.draw_player();
+        self.draw_auto_miners();
 
         set_default_camera();
         if self.debug_mode == DebugMode::Enabled {
-            Self::draw_debug_info(&self.player, &self.blocks);
+            Self::draw_debug_info(
+                self.simulation.seed,
+                &self.simulation.player,
+                &self.simulation.world,
+            );
         }
         self.draw_ui();
     }
@@ -106,64 +362,267 @@ impl Dwarfing {
     // UPDATE FUNCTIONS
     //
 
-    fn apply_gravity(&mut self) {
-        let delta_time = get_frame_time();
-        self.player.speed += GRAVITY * delta_time;
-        self.player.offset_y -= self.player.speed * delta_time;
-    }
-
-    fn update_player_position(&mut self) {
-        // The player should always be at the center, the drawing function draws from the top-left corner
-        self.player.shape.x = screen_width() / 2.0 - self.player.shape.size.x / 2.0;
-        //player.shape.y = block_area_top - player.shape.size.y; // The player should be above the blocks sowe subtract the player size.
-        self.player.shape.y =
-            self.params.block_area_top - self.player.shape.size.y - self.player.offset_y;
-        // Take into account the gravity offset
-    }
-
-    fn update_blocks(&mut self) {
-        // Generate new blocks if needed
-        if self.player.shape.y + screen_height() > self.params.last_row_y {
-            let new_row_y = self.params.last_row_y + BLOCK_SIZE;
-            Self::spawn_row_of_blocks(
-                &mut self.blocks,
-                self.params.needed_x,
-                new_row_y,
-                &self.resources,
-            );
-            self.params.last_row_y = new_row_y;
+    // One fixed step of the simulation, every recorded replay frame is a tick
+    fn tick(&mut self) {
+        let input = self.tick_input();
+        if let ReplayState::Recording(replay) = &mut self.replay {
+            replay.frames.push(ReplayFrame::new(&input, TICK));
+        }
+
+        let events = self
+            .simulation
+            .update(&input, TICK, vec2(screen_width(), screen_height()));
+        self.handle_events(events);
+
+        if let ReplayState::Recording(replay) = &mut self.replay {
+            replay.final_hash = Some(self.simulation.state_hash());
         }
     }
 
-    fn remove_off_screen_blocks(&mut self) {
-        self.blocks
-            .retain(|block| block.shape.y > self.player.shape.y - screen_height());
+    // Feeds the recorded frames instead of the keyboard and mouse, F speeds it up
+    fn update_playback(&mut self, ticks: usize) {
+        let ReplayState::Playback {
+            replay,
+            frame,
+            speed,
+        } = &mut self.replay
+        else {
+            return;
+        };
+
+        if is_key_pressed(KeyCode::F) {
+            *speed = (*speed + 1) % PLAYBACK_SPEEDS.len();
+        }
+
+        let mut events = Vec::new();
+        for _ in 0..ticks * PLAYBACK_SPEEDS[*speed] {
+            let Some(recorded) = replay.frames.get(*frame) else {
+                break;
+            };
+            events.extend(self.simulation.update(
+                &recorded.input(),
+                recorded.delta_time,
+                replay.viewport(),
+            ));
+            *frame += 1;
+        }
+        self.handle_events(events);
     }
 
-    fn player_collision(&mut self) {
-        // Collision detection and resolution
-        for block in &self.blocks {
-            if !block.is_destroyed() && Self::check_collision(&self.player.shape, &block.shape) {
-                Self::resolve_collision(&mut self.player, &block.shape, self.params.block_area_top);
+    // Held keys and the aim only matter as they are on the next tick, presses pile up until then
+    fn collect_input(&mut self) {
+        let mut move_x = 0.0;
+        if is_key_down(KeyCode::A) || is_key_down(KeyCode::Left) {
+            move_x -= 1.0;
+        }
+        if is_key_down(KeyCode::D) || is_key_down(KeyCode::Right) {
+            move_x += 1.0;
+        }
+        let mut move_y = 0.0;
+        if is_key_down(KeyCode::W) || is_key_down(KeyCode::Up) {
+            move_y -= 1.0;
+        }
+        if is_key_down(KeyCode::S) || is_key_down(KeyCode::Down) {
+            move_y += 1.0;
+        }
+
+        // Look back up at the dug out world, walking brings the camera back to the player
+        let (_, wheel) = mouse_wheel();
+        if move_x != 0.0 {
+            self.look_offset = 0.0;
+        } else if wheel != 0.0 && !self.is_shop_open && !self.is_crafting_open {
+            let max_offset = self.player_position().y.max(0.0);
+            self.look_offset =
+                (self.look_offset + wheel.signum() * LOOK_STEP).clamp(0.0, max_offset);
+        }
+
+        let aim = Some(self.mouse_world_position());
+        let view_top = self.camera().screen_to_world(vec2(0.0, 0.0)).y;
+        let input = &mut self.input;
+        input.move_x = move_x;
+        input.move_y = move_y;
+        input.jump_pressed |= is_key_pressed(KeyCode::Space);
+        input.aim = aim;
+        input.view_top = Some(view_top);
+        input.mine_pressed |= is_mouse_button_pressed(MouseButton::Left);
+        input.commands.append(&mut self.pending_commands);
+        if is_key_pressed(KeyCode::E) {
+            input.commands.push(Command::PlaceLadder);
+        }
+        if is_key_pressed(KeyCode::C) && self.simulation.shop_enabled {
+            self.is_crafting_open = !self.is_crafting_open;
+        }
+        for ((ability_id, _), key) in self
+            .resources
+            .definitions
+            .upgrades
+            .abilities()
+            .zip(ABILITY_KEYS)
+        {
+            if is_key_pressed(key) {
+                input.commands.push(Command::UseAbility(ability_id));
             }
         }
     }
 
-    fn handle_input(&mut self) {
-        if is_mouse_button_down(MouseButton::Left) {
-            self.player.sprite.set_animation(1);
-            if is_mouse_button_pressed(MouseButton::Left) {
-                Self::play_low_sound_once(&self.resources.pickaxe_sound);
-                Self::destroy_touching_blocks(&mut self.blocks, &self.player, &mut self.score);
+    // Presses are used up by the first tick, held keys carry over to the next one
+    fn tick_input(&mut self) -> SimInput {
+        SimInput {
+            move_x: self.input.move_x,
+            move_y: self.input.move_y,
+            jump_pressed: std::mem::take(&mut self.input.jump_pressed),
+            aim: self.input.aim,
+            mine_pressed: std::mem::take(&mut self.input.mine_pressed),
+            commands: std::mem::take(&mut self.input.commands),
+            view_top: self.input.view_top,
+        }
+    }
+
+    fn handle_events(&mut self, events: Vec<SimEvent>) {
+        for event in events {
+            match event {
+                SimEvent::SoundRequested(SoundEffect::Pickaxe) | SimEvent::AbilityUsed(_) => {
+                    Self::play_low_sound_once(&self.resources.pickaxe_sound);
+                }
+                SimEvent::PickaxeChanged(pickaxe) => {
+                    self.swap_player_texture();
+
+                    let pickaxe = self.resources.definitions.upgrades.pickaxe(pickaxe);
+                    self.profile
+                        .stats
+                        .pickaxes_bought
+                        .insert(pickaxe.id.clone());
+                }
+                SimEvent::PickaxeBroken(pickaxe) => {
+                    // Back to the basic sprite until it's repaired
+                    self.swap_player_texture();
+                    let pickaxe = self.resources.definitions.upgrades.pickaxe(pickaxe);
+                    self.toasts.push((
+                        format!("Your {} broke, repair it at the base", pickaxe.name),
+                        TOAST_DURATION,
+                    ));
+                }
+                SimEvent::PickaxeRepaired(_) => self.swap_player_texture(),
+                SimEvent::BiomeEntered(biome) => {
+                    self.switch_music(biome);
+                    self.biome_banner = Some((biome, BIOME_BANNER_DURATION));
+                }
+                SimEvent::BlockBroken { block_id, .. } => {
+                    let block = self.resources.definitions.blocks.get(block_id);
+                    self.profile.stats.block(&block.id).destroyed += 1;
+                }
+                SimEvent::BlockClicked(block_id) => {
+                    let block = self.resources.definitions.blocks.get(block_id);
+                    self.profile.stats.block(&block.id).clicks += 1;
+                }
+                SimEvent::BlockDamaged { block_id, damage } => {
+                    let block = self.resources.definitions.blocks.get(block_id);
+                    self.profile.stats.block(&block.id).damage += damage.max(0) as u64;
+                }
+                SimEvent::EnemyKilled { .. } => self.profile.stats.enemies_killed += 1,
+                SimEvent::GoldEarned(gold) => {
+                    self.profile.stats.gold_earned += gold.max(0) as u64;
+                }
+                SimEvent::GoldSpent(gold) => {
+                    self.profile.stats.gold_spent += gold.max(0) as u64;
+                }
+                SimEvent::InventoryFull(item_id) => {
+                    // Every block broken with a full backpack would say it again otherwise
+                    let item = self.resources.definitions.items.get(item_id);
+                    let text = format!("Backpack full, {} left behind", item.name);
+                    if !self.toasts.iter().any(|(other, _)| *other == text) {
+                        self.toasts.push((text, TOAST_DURATION));
+                    }
+                }
+                SimEvent::ItemsSold(gold) => {
+                    self.toasts.push((
+                        format!("Sold the backpack for {} gold", gold),
+                        TOAST_DURATION,
+                    ));
+                }
+                SimEvent::RecipeCrafted(recipe_id) => {
+                    let recipe = self.resources.definitions.recipes.get(recipe_id);
+                    self.toasts
+                        .push((format!("Crafted: {}", recipe.name), TOAST_DURATION));
+                }
+                SimEvent::ScoreChanged
+                | SimEvent::PlayerHurt
+                | SimEvent::PlayerDied
+                | SimEvent::ItemPicked(_) => {}
             }
+        }
+    }
+
+    fn swap_player_texture(&mut self) {
+        let texture = self
+            .resources
+            .player_texture(self.simulation.effective_pickaxe())
+            .clone();
+        self.player_sprite.swap_texture(texture);
+    }
+
+    fn update_sprite(&mut self) {
+        if is_mouse_button_down(MouseButton::Left) {
+            self.player_sprite.sprite.set_animation(1);
         } else {
-            self.player.sprite.set_animation(0);
+            self.player_sprite.sprite.set_animation(0);
+        }
+
+        self.player_sprite.sprite.update();
+    }
+
+    fn update_achievements(&mut self) {
+        let stats = &mut self.profile.stats;
+        stats.seconds_played += get_frame_time();
+        stats.deepest_row = stats.deepest_row.max(self.simulation.deepest_row);
+
+        let unlocked = self
+            .resources
+            .definitions
+            .achievements
+            .check(stats, &mut self.profile.achievements);
+        if !unlocked.is_empty() {
+            for achievement in unlocked {
+                self.toasts.push((
+                    format!("Achievement unlocked: {}", achievement.name),
+                    TOAST_DURATION,
+                ));
+            }
+            if let Err(err) = profile::write(&self.profile) {
+                eprintln!("Could not save the profile: {}", err);
+            }
         }
 
-        self.player.sprite.update();
+        for (_, timer) in &mut self.toasts {
+            *timer -= get_frame_time();
+        }
+        self.toasts.retain(|(_, timer)| *timer > 0.0);
+    }
+
+    fn update_biome_banner(&mut self) {
+        if let Some((_, timer)) = &mut self.biome_banner {
+            *timer -= get_frame_time();
+            if *timer <= 0.0 {
+                self.biome_banner = None;
+            }
+        }
+    }
+
+    fn handle_autosave(&mut self) {
+        self.autosave_timer += get_frame_time();
+        if self.autosave_timer >= AUTOSAVE_INTERVAL {
+            self.autosave_timer = 0.0;
+            if let Err(err) = self.save() {
+                eprintln!("Autosave failed: {}", err);
+            }
+        }
     }
 
     fn handle_camera(&self) {
+        set_camera(&self.camera());
+    }
+
+    fn camera(&self) -> Camera2D {
         let mut camera = Camera2D::from_display_rect(Rect {
             x: 0.0,
             y: 0.0,
@@ -174,10 +633,20 @@ impl Dwarfing {
         // Fix the Y axis flipping and re-center the camera
         camera.zoom = vec2(1. / screen_width() * 4., 1. / screen_height() * 4.);
 
-        // Manually center the camera on the middle of the screen
-        camera.target = vec2(screen_width() / 2.0, self.player.shape.y);
+        // Follow the player, but don't show what's outside of the world on the sides
+        let half_view_width = screen_width() / 4.0;
+        let player_position = self.player_position();
+        let target_x = (player_position.x + self.simulation.player.shape.size.x / 2.0).clamp(
+            half_view_width,
+            (self.simulation.world_width() - half_view_width).max(half_view_width),
+        );
+        camera.target = vec2(target_x, player_position.y - self.look_offset);
 
-        set_camera(&camera);
+        camera
+    }
+
+    fn mouse_world_position(&self) -> Vec2 {
+        self.camera().screen_to_world(mouse_position().into())
     }
 
     //
@@ -185,91 +654,162 @@ impl Dwarfing {
     //
 
     fn draw_blocks(&self) {
-        for block in &self.blocks {
-            if !block.is_destroyed() {
-                match self.debug_mode {
-                    DebugMode::Enabled => {
-                        draw_rectangle_lines(
-                            block.shape.x,
-                            block.shape.y,
-                            block.shape.size.x,
-                            block.shape.size.y,
-                            1.0,
-                            block.shape.color,
-                        );
-                    }
-                    DebugMode::Disabled => {
-                        //draw_rectangle(
-                        //    block.shape.x,
-                        //    block.shape.y,
-                        //    block.shape.size.x,
-                        //    block.shape.size.y,
-                        //    block.shape.color,
-                        //);
-
-                        let texture = match &block.block_type {
-                            crate::block::BlockType::Dirt {
-                                base_hp: _,
-                                hp: _,
-                                texture,
-                            } => texture,
-                            crate::block::BlockType::Rock {
-                                base_hp: _,
-                                hp: _,
-                                texture,
-                            } => texture,
-                            crate::block::BlockType::Gold {
-                                base_hp: _,
-                                hp: _,
-                                texture,
-                            } => texture,
-                        };
-
-                        draw_texture_ex(
-                            texture,
-                            block.shape.x,
-                            block.shape.y,
-                            WHITE,
-                            DrawTextureParams {
-                                dest_size: Some(Vec2 {
-                                    x: block.shape.size.x,
-                                    y: block.shape.size.y,
-                                }),
-                                source: Some(block.texture_selector()),
-                                ..Default::default()
-                            },
-                        );
-                    }
+        let camera = self.camera();
+        let top = camera.screen_to_world(vec2(0.0, 0.0)).y;
+        let bottom = camera.screen_to_world(vec2(0.0, screen_height())).y;
+        for block in self.simulation.world.visible_blocks(top, bottom) {
+            match self.debug_mode {
+                DebugMode::Enabled => {
+                    draw_rectangle_lines(
+                        block.shape.x,
+                        block.shape.y,
+                        block.shape.size.x,
+                        block.shape.size.y,
+                        1.0,
+                        block.shape.color,
+                    );
                 }
+                DebugMode::Disabled => {
+                    //draw_rectangle(
+                    //    block.shape.x,
+                    //    block.shape.y,
+                    //    block.shape.size.x,
+                    //    block.shape.size.y,
+                    //    block.shape.color,
+                    //);
+
+                    let def = self.resources.definitions.blocks.get(block.block_id);
+
+                    let tint = def
+                        .tint
+                        .map(|[r, g, b]| Color::new(r, g, b, 1.0))
+                        .unwrap_or(WHITE);
+
+                    draw_texture_ex(
+                        self.resources.block_texture(block.block_id),
+                        block.shape.x,
+                        block.shape.y,
+                        tint,
+                        DrawTextureParams {
+                            dest_size: Some(Vec2 {
+                                x: block.shape.size.x,
+                                y: block.shape.size.y,
+                            }),
+                            source: Some(block.texture_selector(def)),
+                            ..Default::default()
+                        },
+                    );
+                }
+            }
+        }
+    }
+
+    // A little hut on the surface, where the shop is
+    fn draw_base(&self) {
+        const WIDTH: f32 = BLOCK_SIZE * 3.0;
+        const HEIGHT: f32 = BLOCK_SIZE * 2.0;
+
+        let x = (self.simulation.world_width() - WIDTH) / 2.0;
+        let y = self.simulation.surface_y() - HEIGHT;
+        draw_rectangle(x, y, WIDTH, HEIGHT, Color::new(0.45, 0.3, 0.15, 1.0));
+        draw_triangle(
+            vec2(x - 8.0, y),
+            vec2(x + WIDTH + 8.0, y),
+            vec2(x + WIDTH / 2.0, y - BLOCK_SIZE),
+            Color::new(0.55, 0.15, 0.1, 1.0),
+        );
+        draw_rectangle(
+            x + WIDTH / 2.0 - 10.0,
+            y + HEIGHT - 36.0,
+            20.0,
+            36.0,
+            Color::new(0.2, 0.12, 0.05, 1.0),
+        );
+    }
+
+    fn draw_ladders(&self) {
+        let color = Color::new(0.6, 0.4, 0.2, 1.0);
+        for (column, row) in self.simulation.world.ladders() {
+            let x = column as f32 * BLOCK_SIZE;
+            let y = self.simulation.surface_y() + (row - 1) as f32 * BLOCK_SIZE;
+            draw_rectangle(x + 6.0, y, 3.0, BLOCK_SIZE, color);
+            draw_rectangle(x + BLOCK_SIZE - 9.0, y, 3.0, BLOCK_SIZE, color);
+            for rung in 0..4 {
+                let rung_y = y + 4.0 + rung as f32 * 8.0;
+                draw_rectangle(x + 6.0, rung_y, BLOCK_SIZE - 12.0, 2.0, color);
+            }
+        }
+    }
+
+    fn draw_enemies(&self) {
+        for enemy in &self.simulation.enemies {
+            let shape = &enemy.shape;
+            let Vec2 { x, y } = self.interpolate(enemy.previous_position, shape);
+            draw_rectangle(x, y, shape.size.x, shape.size.y, shape.color);
+            draw_rectangle_lines(x, y, shape.size.x, shape.size.y, 1.0, BLACK);
+
+            // Small health bar on top of hurt enemies
+            let max_hp = self.resources.definitions.enemies.get(enemy.enemy_id).hp;
+            if enemy.hp < max_hp {
+                let hp_width = shape.size.x * enemy.hp as f32 / max_hp as f32;
+                draw_rectangle(x, y - 4.0, shape.size.x, 2.0, DARKGRAY);
+                draw_rectangle(x, y - 4.0, hp_width, 2.0, RED);
             }
         }
     }
 
+    fn draw_mining_target(&self) {
+        if let Some(block) = self
+            .simulation
+            .mining_target(Some(self.mouse_world_position()))
+            .and_then(|tile| self.simulation.world.get(tile))
+        {
+            draw_rectangle_lines(
+                block.shape.x,
+                block.shape.y,
+                block.shape.size.x,
+                block.shape.size.y,
+                2.0,
+                Color::new(1.0, 1.0, 1.0, 0.6),
+            );
+        }
+    }
+
     fn draw_player(&self) {
+        let player = &self.simulation.player;
+        let position = self.player_position();
         match self.debug_mode {
             DebugMode::Enabled => {
                 draw_rectangle(
-                    self.player.shape.x,
-                    self.player.shape.y,
-                    self.player.shape.size.x,
-                    self.player.shape.size.y,
-                    self.player.shape.color,
+                    position.x,
+                    position.y,
+                    player.shape.size.x,
+                    player.shape.size.y,
+                    player.shape.color,
                 );
             }
             DebugMode::Disabled => {
-                let frame = self.player.sprite.frame();
+                let frame = self.player_sprite.sprite.frame();
+
+                // Flash red while recovering from a hit
+                let color = if player.hurt_timer > 0.0 {
+                    Color::new(1.0, 0.4, 0.4, 1.0)
+                } else {
+                    WHITE
+                };
 
                 draw_texture_ex(
-                    &self.player.texture,
-                    self.player.shape.x,
-                    self.player.shape.y,
-                    WHITE,
+                    &self.player_sprite.texture,
+                    position.x,
+                    position.y,
+                    color,
                     DrawTextureParams {
                         dest_size: Some(Vec2 {
-                            x: self.player.shape.size.x,
-                            y: self.player.shape.size.y,
+                            x: player.shape.size.x,
+                            y: player.shape.size.y,
                         }),
                         source: Some(frame.source_rect),
+                        flip_x: player.facing == Facing::Left,
                         ..Default::default()
                     },
                 );
@@ -277,8 +817,24 @@ impl Dwarfing {
         }
     }
 
+    // Little helpers buzzing around the player, one per owned auto-miner (up to a limit)
+    fn draw_auto_miners(&self) {
+        const MAX_DRAWN: u32 = 12;
+
+        let center = self.player_position() + self.simulation.player.shape.size / 2.0;
+        let count = self.simulation.auto_miners.total().min(MAX_DRAWN);
+        for index in 0..count {
+            let angle =
+                get_time() as f32 * 2.0 + index as f32 * std::f32::consts::TAU / count as f32;
+            let position = center + vec2(angle.cos(), angle.sin()) * 24.0;
+            draw_rectangle(position.x - 3.0, position.y - 3.0, 6.0, 6.0, ORANGE);
+        }
+    }
+
     fn draw_ui(&mut self) {
-        let score_text = format!("Score = {}", &self.score.current_score);
+        let score = &self.simulation.score;
+
+        let score_text = format!("Score = {}", &score.current_score);
         macroquad::ui::root_ui().label(
             Vec2::new(
                 screen_width() - 10.0 - measure_text(score_text.as_str(), None, 28, 1.0).width,
@@ -287,7 +843,7 @@ impl Dwarfing {
             score_text.as_str(),
         );
 
-        let destroyed_blocks_text = format!("Destroyed Blocks = {}", &self.score.blocks_destroyed);
+        let destroyed_blocks_text = format!("Destroyed Blocks = {}", &score.blocks_destroyed);
         macroquad::ui::root_ui().label(
             Vec2::new(
                 screen_width()
@@ -298,7 +854,7 @@ impl Dwarfing {
             destroyed_blocks_text.as_str(),
         );
 
-        let gold_text = format!("Gold = {}", &self.score.gold);
+        let gold_text = format!("Gold = {}", &score.gold);
         macroquad::ui::root_ui().label(
             Vec2::new(
                 screen_width() - 10.0 - measure_text(gold_text.as_str(), None, 28, 1.0).width,
@@ -307,18 +863,99 @@ impl Dwarfing {
             gold_text.as_str(),
         );
 
+        let health_text = format!("Health = {}/{}", self.simulation.player.health, MAX_HEALTH);
+        macroquad::ui::root_ui().label(
+            Vec2::new(
+                screen_width() - 10.0 - measure_text(health_text.as_str(), None, 28, 1.0).width,
+                85.0,
+            ),
+            health_text.as_str(),
+        );
+
+        let ladders_text = format!("Ladders = {} (E to place)", self.simulation.ladders);
+        macroquad::ui::root_ui().label(
+            Vec2::new(
+                screen_width() - 10.0 - measure_text(ladders_text.as_str(), None, 28, 1.0).width,
+                110.0,
+            ),
+            ladders_text.as_str(),
+        );
+
+        if let Some(mode_text) = self.rules.hud_text(&self.simulation) {
+            macroquad::ui::root_ui().label(
+                Vec2::new(
+                    screen_width() - 10.0 - measure_text(mode_text.as_str(), None, 28, 1.0).width,
+                    135.0,
+                ),
+                mode_text.as_str(),
+            );
+        }
+
+        self.draw_inventory();
+
+        // The shop is at the surface base, it closes when walking away from it
+        let is_at_base = self.simulation.is_at_base();
+        if !is_at_base {
+            self.is_shop_open = false;
+        }
+        if self.simulation.shop_enabled {
+            let shop_position = Vec2::new(screen_width() - 170.0, screen_height() - 100.0);
+            if !is_at_base {
+                macroquad::ui::root_ui().label(shop_position, "Shop at the base");
+            } else if macroquad::ui::root_ui().button(shop_position, String::from("Shop")) {
+                self.is_shop_open = true;
+            }
+
+            // Crafting works anywhere, so the queue keeps going while digging
+            if macroquad::ui::root_ui().button(
+                Vec2::new(screen_width() - 170.0, screen_height() - 140.0),
+                String::from("Crafting"),
+            ) {
+                self.is_crafting_open = true;
+            }
+        }
+
         if macroquad::ui::root_ui().button(
-            Vec2::new(screen_width() - 170.0, screen_height() - 100.0),
-            String::from("Shop"),
+            Vec2::new(screen_width() - 290.0, screen_height() - 100.0),
+            String::from("Retire"),
         ) {
-            self.is_shop_open = true;
+            self.is_retire_open = true;
+        }
+
+        if self.is_retire_open {
+            self.draw_retire_confirmation();
+        }
+
+        if let Some(runes) = self.simulation.prestige_reward() {
+            if macroquad::ui::root_ui().button(
+                Vec2::new(screen_width() - 290.0, screen_height() - 180.0),
+                String::from("Prestige"),
+            ) {
+                self.is_prestige_open = true;
+            }
+
+            if self.is_prestige_open {
+                self.draw_prestige_confirmation(runes);
+            }
         }
 
         if self.is_shop_open {
+            let gold = self.simulation.score.gold;
+            let current_pickaxe = self.simulation.player.current_pickaxe;
+            let abilities = &self.simulation.abilities;
+            let auto_miners = &self.simulation.auto_miners;
+            let inventory = &self.simulation.inventory;
+            let durability = self.simulation.player.durability;
+            let max_durability = self.simulation.max_durability();
+            let repair_price = self.simulation.repair_price();
+            let sell_value = self.simulation.sell_value();
+            let definitions = self.resources.definitions.clone();
+            let catalog = &definitions.upgrades;
+
             //let window_skin = macroquad::ui::root_ui().default_skin();
             //root_ui().push_skin(&window_skin);
             root_ui().pop_skin(); // TODO
-            macroquad::ui::widgets::Window::new(hash!(), vec2(400., 200.), vec2(320., 400.))
+            macroquad::ui::widgets::Window::new(hash!(), vec2(400., 150.), vec2(320., 500.))
                 .label("Shop")
                 .close_button(false)
                 .titlebar(false)
@@ -328,174 +965,606 @@ impl Dwarfing {
                         self.is_shop_open = false;
                     }
 
-                    // TODO: Int's of price... should not be hard-coded like this?
-                    macroquad::ui::widgets::Group::new(hash!("first_upgrade"), vec2(320., 80.)).ui(
-                        ui,
-                        |ui| {
-                            ui.label(Vec2::splat(10.), "Iron Pickaxe");
-                            ui.label(vec2(200., 10.), "Price: 50 Gold");
-                            if self.score.gold >= 50
-                                && self.player.current_pickaxe != Pickaxe::Iron
-                                && ui.button(vec2(10., 40.), "Buy")
-                            {
-                                self.player.current_pickaxe = Pickaxe::Iron;
-                                self.player
-                                    .swap_texture(self.resources.player_texture_iron.clone());
-                                self.score.gold -= 50;
+                    macroquad::ui::widgets::Group::new(hash!(), vec2(320., 80.)).ui(ui, |ui| {
+                        ui.label(Vec2::splat(10.), "Sell the backpack");
+                        ui.label(vec2(200., 10.), &format!("Value: {} Gold", sell_value));
+                        if inventory.is_empty() {
+                            ui.label(vec2(10., 40.), "Nothing to sell");
+                        } else if ui.button(vec2(10., 40.), "Sell") {
+                            self.pending_commands.push(Command::SellItems);
+                        }
+                    });
+
+                    // One entry per pickaxe in upgrades.json, the starting one can't be bought
+                    for (pickaxe_id, pickaxe) in catalog.pickaxes() {
+                        if pickaxe_id == catalog.starting_pickaxe() {
+                            continue;
+                        }
+
+                        macroquad::ui::widgets::Group::new(
+                            hash!("pickaxe", &pickaxe.id),
+                            vec2(320., 80.),
+                        )
+                        .ui(ui, |ui| {
+                            ui.label(Vec2::splat(10.), &pickaxe.name);
+                            ui.label(vec2(200., 10.), &format!("Price: {} Gold", pickaxe.price));
+                            match catalog.pickaxe_blocker(current_pickaxe, pickaxe_id, gold) {
+                                Some(reason) => ui.label(vec2(10., 40.), &reason),
+                                None => {
+                                    if ui.button(vec2(10., 40.), "Buy") {
+                                        self.pending_commands.push(Command::BuyPickaxe(pickaxe_id));
+                                    }
+                                }
                             }
-                        },
-                    );
+                        });
+                    }
+
+                    macroquad::ui::widgets::Group::new(hash!(), vec2(320., 80.)).ui(ui, |ui| {
+                        ui.label(Vec2::splat(10.), "Repair the pickaxe");
+                        ui.label(vec2(200., 10.), &format!("Price: {} Gold", repair_price));
+                        ui.label(
+                            vec2(200., 40.),
+                            &format!("{}/{}", durability, max_durability),
+                        );
+                        if durability >= max_durability {
+                            ui.label(vec2(10., 40.), "Not damaged");
+                        } else if gold < repair_price {
+                            ui.label(vec2(10., 40.), "Not enough gold");
+                        } else if ui.button(vec2(10., 40.), "Repair") {
+                            self.pending_commands.push(Command::RepairPickaxe);
+                        }
+                    });
+
+                    for (ability_id, ability) in catalog.abilities() {
+                        macroquad::ui::widgets::Group::new(
+                            hash!("ability", &ability.id),
+                            vec2(320., 80.),
+                        )
+                        .ui(ui, |ui| {
+                            ui.label(Vec2::splat(10.), &ability.name);
+                            ui.label(vec2(200., 10.), &format!("Price: {} Gold", ability.price));
+                            let owned = abilities.is_owned(ability_id);
+                            match catalog.ability_blocker(owned, ability_id, gold) {
+                                Some(reason) => ui.label(vec2(10., 40.), &reason),
+                                None => {
+                                    if ui.button(vec2(10., 40.), "Buy") {
+                                        self.pending_commands.push(Command::BuyAbility(ability_id));
+                                    }
+                                }
+                            }
+                        });
+                    }
+
+                    let ladders = &catalog.ladders;
+                    macroquad::ui::widgets::Group::new(hash!(), vec2(320., 80.)).ui(ui, |ui| {
+                        ui.label(Vec2::splat(10.), &format!("{} Ladders", ladders.bundle));
+                        ui.label(vec2(200., 10.), &format!("Price: {} Gold", ladders.price));
+                        ui.label(
+                            vec2(200., 40.),
+                            &format!("Owned: {}", self.simulation.ladders),
+                        );
+                        if gold < ladders.price {
+                            ui.label(vec2(10., 40.), "Not enough gold");
+                        } else if ui.button(vec2(10., 40.), "Buy") {
+                            self.pending_commands.push(Command::BuyLadders);
+                        }
+                    });
+
+                    for (upgrade_id, upgrade) in catalog.storage_upgrades() {
+                        macroquad::ui::widgets::Group::new(
+                            hash!("storage", &upgrade.id),
+                            vec2(320., 80.),
+                        )
+                        .ui(ui, |ui| {
+                            ui.label(Vec2::splat(10.), &upgrade.name);
+                            ui.label(vec2(200., 10.), &format!("Price: {} Gold", upgrade.price));
+                            let owned = inventory.owned_storage();
+                            match catalog.storage_blocker(owned, upgrade_id, gold) {
+                                Some(reason) => ui.label(vec2(10., 40.), &reason),
+                                None => {
+                                    if ui.button(vec2(10., 40.), "Buy") {
+                                        self.pending_commands
+                                            .push(Command::BuyStorageUpgrade(upgrade_id));
+                                    }
+                                }
+                            }
+                        });
+                    }
 
-                    macroquad::ui::widgets::Group::new(hash!("second_upgrade"), vec2(320., 80.))
+                    // Auto-miners can be bought over and over, each one more expensive than the last
+                    for (auto_miner_id, auto_miner) in catalog.auto_miners() {
+                        macroquad::ui::widgets::Group::new(
+                            hash!("auto_miner", &auto_miner.id),
+                            vec2(320., 80.),
+                        )
                         .ui(ui, |ui| {
-                            ui.label(Vec2::splat(10.), "Gold Pickaxe");
-                            ui.label(vec2(200., 10.), "Price: 150 Gold");
-                            if self.score.gold >= 150
-                                && self.player.current_pickaxe != Pickaxe::Gold
-                                && ui.button(vec2(10., 40.), "Buy")
-                            {
-                                self.player.current_pickaxe = Pickaxe::Gold;
-                                self.player
-                                    .swap_texture(self.resources.player_texture_gold.clone());
-                                self.score.gold -= 150;
+                            let owned = auto_miners.count(auto_miner_id);
+                            let price = auto_miner.price(owned);
+                            ui.label(Vec2::splat(10.), &auto_miner.name);
+                            ui.label(vec2(200., 10.), &format!("Price: {} Gold", price));
+                            ui.label(vec2(200., 40.), &format!("Owned: {}", owned));
+                            if gold < price {
+                                ui.label(vec2(10., 40.), "Not enough gold");
+                            } else if ui.button(vec2(10., 40.), "Buy") {
+                                self.pending_commands
+                                    .push(Command::BuyAutoMiner(auto_miner_id));
                             }
                         });
+                    }
                 });
             //root_ui().pop_skin();
         }
+
+        if self.is_crafting_open {
+            self.draw_crafting();
+        }
+
+        self.draw_abilities();
+        self.draw_durability();
+        self.draw_biome_banner();
+        self.draw_toasts();
+        self.draw_offline_report();
+        self.draw_replay_info();
     }
 
-    fn draw_background(&self) {
-        let background_y = if self.player.shape.y <= screen_height() {
-            0.0 // Keep background fixed at top when player is in upper half
-        } else {
-            self.player.shape.y - screen_height() / 2. // Scroll background when player is lower
+    // Under the rest of the HUD on the right, one line per item the player is carrying
+    fn draw_inventory(&self) {
+        let definitions = &self.resources.definitions;
+        let inventory = &self.simulation.inventory;
+        let mut lines = vec![format!(
+            "Backpack = {:.1}/{:.0} kg, {}/{} slots",
+            inventory.weight(&definitions.items, &definitions.upgrades),
+            inventory.max_weight(&definitions.upgrades),
+            inventory.slots_used(&definitions.items),
+            inventory.max_slots(&definitions.upgrades),
+        )];
+        for (item_id, item) in definitions.items.iter() {
+            let count = inventory.count(item_id);
+            if count > 0 {
+                lines.push(format!("{} x{}", item.name, count));
+            }
+        }
+        if let Some(job) = self.simulation.crafting.jobs().next() {
+            let recipe = definitions.recipes.get(job.recipe);
+            lines.push(if job.elapsed >= recipe.time {
+                format!("{} waiting for room in the backpack", recipe.name)
+            } else {
+                format!(
+                    "Crafting {} {:.0}%",
+                    recipe.name,
+                    job.elapsed / recipe.time * 100.0
+                )
+            });
+        }
+
+        for (index, line) in lines.iter().enumerate() {
+            macroquad::ui::root_ui().label(
+                Vec2::new(
+                    screen_width() - 10.0 - measure_text(line.as_str(), None, 28, 1.0).width,
+                    160.0 + index as f32 * 25.0,
+                ),
+                line.as_str(),
+            );
+        }
+    }
+
+    // The queue on top, then one entry per recipe in recipes.json
+    fn draw_crafting(&mut self) {
+        let definitions = self.resources.definitions.clone();
+        let simulation = &self.simulation;
+
+        root_ui().pop_skin(); // TODO, same as the shop
+        macroquad::ui::widgets::Window::new(hash!(), vec2(40., 150.), vec2(340., 500.))
+            .label("Crafting")
+            .close_button(false)
+            .titlebar(false)
+            .movable(false)
+            .ui(&mut macroquad::ui::root_ui(), |ui| {
+                if ui.button(Vec2::new(10., 10.), "Close") {
+                    self.is_crafting_open = false;
+                }
+
+                macroquad::ui::widgets::Group::new(hash!(), vec2(340., 150.)).ui(ui, |ui| {
+                    ui.label(
+                        Vec2::splat(10.),
+                        &format!(
+                            "Queue ({}/{})",
+                            simulation.crafting.jobs().count(),
+                            MAX_QUEUE
+                        ),
+                    );
+                    for (index, job) in simulation.crafting.jobs().enumerate() {
+                        let recipe = definitions.recipes.get(job.recipe);
+                        ui.label(
+                            vec2(10., 35. + index as f32 * 22.),
+                            &format!(
+                                "{}. {} ({:.0}/{:.0}s)",
+                                index + 1,
+                                recipe.name,
+                                job.elapsed,
+                                recipe.time
+                            ),
+                        );
+                    }
+                });
+
+                for (recipe_id, recipe) in definitions.recipes.iter() {
+                    macroquad::ui::widgets::Group::new(
+                        hash!("recipe", &recipe.id),
+                        vec2(340., 80.),
+                    )
+                    .ui(ui, |ui| {
+                        let ingredients: Vec<String> = recipe
+                            .ingredients
+                            .iter()
+                            .map(|&(item, count)| {
+                                format!("{} {}", count, definitions.items.get(item).name)
+                            })
+                            .collect();
+                        ui.label(Vec2::splat(10.), &recipe.name);
+                        ui.label(vec2(220., 10.), &format!("{:.0}s", recipe.time));
+                        ui.label(vec2(10., 30.), &ingredients.join(", "));
+                        match simulation.craft_blocker(recipe_id) {
+                            Some(reason) => ui.label(vec2(10., 52.), &reason),
+                            None => {
+                                if ui.button(vec2(10., 52.), "Queue") {
+                                    self.pending_commands.push(Command::QueueRecipe(recipe_id));
+                                }
+                            }
+                        }
+                    });
+                }
+            });
+    }
+
+    fn draw_replay_info(&self) {
+        let ReplayState::Playback {
+            replay,
+            frame,
+            speed,
+        } = &self.replay
+        else {
+            return;
         };
 
-        draw_texture(
-            &self.resources.game_background_texture,
-            0.,
-            background_y,
-            WHITE,
+        let text = match self.playback_result() {
+            Some(Ok(())) => {
+                String::from("Replay finished, same final state as recorded. Esc: Menu")
+            }
+            Some(Err(err)) => format!("Replay finished, {}. Esc: Menu", err),
+            None => format!(
+                "Replay x{} | Frame {}/{} | F: Fast-forward, Esc: Menu",
+                PLAYBACK_SPEEDS[*speed],
+                frame,
+                replay.frames.len()
+            ),
+        };
+        root_ui().label(
+            vec2(
+                (screen_width() - measure_text(&text, None, 28, 1.0).width) / 2.0,
+                screen_height() - 40.0,
+            ),
+            &text,
         );
     }
 
-    //
-    // HELPERS
-    //
-
-    fn spawn_row_of_blocks(blocks: &mut Vec<Block>, needed_x: i32, y: f32, resources: &Resources) {
-        for x in 0..needed_x {
-            let shape = Shape {
-                x: x as f32 * BLOCK_SIZE,
+    // Stacked in the top left corner, newest at the bottom
+    fn draw_toasts(&self) {
+        for (index, (text, timer)) in self.toasts.iter().enumerate() {
+            let alpha = timer.min(1.0);
+            let y = 100.0 + index as f32 * 40.0;
+            let size = measure_text(text, None, 24, 1.0);
+            draw_rectangle(
+                10.0,
                 y,
-                size: Vec2::splat(BLOCK_SIZE),
-                color: RED,
-            };
+                size.width + 20.0,
+                32.0,
+                Color::new(0.1, 0.1, 0.1, 0.8 * alpha),
+            );
+            draw_text(
+                text,
+                20.0,
+                y + 22.0,
+                24.0,
+                Color::new(1.0, 0.85, 0.2, alpha),
+            );
+        }
+    }
 
-            // TODO: Move this to a helper function
-            // Select a Random Block
-            let dynamic_rock_cap = (0.3 + (0.001 * (y - 1000.0).max(0.0))).min(0.8); // Cap increases after y = 1000, maxing at 80%
-            let rock_probability = (0.01 * y.ln()).min(dynamic_rock_cap); // Rock probability increases with depth
+    fn draw_prestige_confirmation(&mut self, runes: u32) {
+        let lines = [
+            format!("Reset the world for {} runes?", runes),
+            String::from("Your score, pickaxe, abilities and"),
+            String::from("helpers will be lost."),
+        ];
 
-            let dynamic_gold_cap = (0.02 + (0.001 * (y - 2000.0).max(0.0))).min(0.2); // Cap increases slowly, maxing at 20%
-            let gold_probability = if y > 2000.0 {
-                (0.002 * (y - 2000.0).ln()).min(dynamic_gold_cap) // Gold probability increases after y = 2000
-            } else {
-                0.0 // No gold above y = 2000
-            };
+        macroquad::ui::widgets::Window::new(hash!(), vec2(300., 250.), vec2(456., 220.))
+            .label("Prestige")
+            .titlebar(false)
+            .movable(false)
+            .ui(&mut macroquad::ui::root_ui(), |ui| {
+                for (index, line) in lines.iter().enumerate() {
+                    ui.label(vec2(10., 10. + index as f32 * 30.), line);
+                }
+                if ui.button(vec2(10., 110.), "Reset") {
+                    self.claimed_prestige = Some(runes);
+                }
+                if ui.button(vec2(230., 110.), "Cancel") {
+                    self.is_prestige_open = false;
+                }
+            });
+    }
 
-            let rng_num = rand() as f32 / u32::MAX as f32;
+    fn draw_retire_confirmation(&mut self) {
+        let lines = [
+            String::from("End this run and record your score?"),
+            String::from("The world can't be continued afterwards."),
+        ];
 
-            let block_type = if rng_num < gold_probability {
-                BlockType::Gold {
-                    base_hp: 100,
-                    hp: 100,
-                    texture: resources.gold_block_texture.clone(), // TODO: Can I avoid cloning the textures?
+        macroquad::ui::widgets::Window::new(hash!(), vec2(300., 250.), vec2(456., 220.))
+            .label("Retire")
+            .titlebar(false)
+            .movable(false)
+            .ui(&mut macroquad::ui::root_ui(), |ui| {
+                for (index, line) in lines.iter().enumerate() {
+                    ui.label(vec2(10., 10. + index as f32 * 30.), line);
                 }
-            } else if rng_num < (rock_probability + gold_probability) {
-                BlockType::Rock {
-                    base_hp: 70,
-                    hp: 70,
-                    texture: resources.rock_block_texture.clone(),
+                if ui.button(vec2(10., 110.), "Retire") {
+                    self.pending_commands.push(Command::Retire);
+                    self.is_retire_open = false;
                 }
-            } else {
-                BlockType::Dirt {
-                    base_hp: 50,
-                    hp: 50,
-                    texture: resources.dirt_block_texture.clone(),
+                if ui.button(vec2(230., 110.), "Cancel") {
+                    self.is_retire_open = false;
                 }
-            };
-
-            blocks.push(Block::new(shape, block_type));
-        }
+            });
     }
 
-    fn check_collision(a: &Shape, b: &Shape) -> bool {
-        a.x < b.x + b.size.x && a.x + a.size.x > b.x && a.y < b.y + b.size.y && a.y + a.size.y > b.y
-    }
+    fn draw_offline_report(&mut self) {
+        let Some(report) = &self.offline_report else {
+            return;
+        };
+
+        let minutes = (report.seconds / 60.0) as i32;
+        let lines = [
+            format!("You were away for {}h {}m", minutes / 60, minutes % 60),
+            format!("Your helpers dug {} blocks", report.blocks),
+            format!("and found {} gold", report.gold),
+        ];
 
-    fn resolve_collision(player: &mut Player, block: &Shape, block_area_top: f32) {
-        let player_bottom = player.shape.y + player.shape.size.y;
-        let block_top = block.y;
+        let mut is_closed = false;
+        macroquad::ui::widgets::Window::new(hash!(), vec2(300., 250.), vec2(456., 220.))
+            .label("While you were away")
+            .titlebar(false)
+            .movable(false)
+            .ui(&mut macroquad::ui::root_ui(), |ui| {
+                for (index, line) in lines.iter().enumerate() {
+                    ui.label(vec2(10., 10. + index as f32 * 30.), line);
+                }
+                if ui.button(vec2(10., 110.), "Ok") {
+                    is_closed = true;
+                }
+            });
 
-        if player_bottom > block_top {
-            player.offset_y = block_area_top - block_top;
-            player.speed = 0.0;
+        if is_closed {
+            self.offline_report = None;
         }
     }
 
-    fn destroy_touching_blocks(blocks: &mut [Block], player: &Player, score: &mut Score) {
-        for block in blocks.iter_mut() {
-            if !block.is_destroyed() && Self::check_collision(&player.shape, &block.shape) {
-                // TODO: Move this logic to a more reasonable location? Int's Should not be hard-coded like this?
-                let hp_to_subtract = match player.current_pickaxe {
-                    Pickaxe::Normal => 10,
-                    Pickaxe::Iron => 25,
-                    Pickaxe::Gold => 50,
-                };
+    // One slot per owned ability in the bottom left corner, darkened while on cooldown
+    fn draw_abilities(&self) {
+        const SLOT_SIZE: f32 = 64.0;
+        const SLOT_SPACING: f32 = 10.0;
 
-                let block_destroyed = block.subtract_block_hp(hp_to_subtract);
-                if block_destroyed {
-                    block.update_score(score);
-                }
-                return; // Added a return so you only break one block per click
+        let abilities = &self.simulation.abilities;
+        let slot_y = screen_height() - SLOT_SIZE - SLOT_SPACING;
+        let mut slot_x = SLOT_SPACING;
+        for ((ability_id, ability), key) in self.resources.definitions.upgrades.abilities().zip(1..)
+        {
+            if !abilities.is_owned(ability_id) {
+                continue;
+            }
+
+            draw_rectangle(
+                slot_x,
+                slot_y,
+                SLOT_SIZE,
+                SLOT_SIZE,
+                Color::new(0.0, 0.0, 0.0, 0.6),
+            );
+
+            // The dark overlay shrinks as the cooldown goes down
+            let cooldown = abilities.cooldown(ability_id);
+            if cooldown > 0.0 {
+                let overlay_height = SLOT_SIZE * (cooldown / ability.cooldown).min(1.0);
+                draw_rectangle(
+                    slot_x,
+                    slot_y + SLOT_SIZE - overlay_height,
+                    SLOT_SIZE,
+                    overlay_height,
+                    Color::new(0.6, 0.1, 0.1, 0.6),
+                );
+                draw_text(
+                    &format!("{:.0}", cooldown.ceil()),
+                    slot_x + SLOT_SIZE / 2.0 - 8.0,
+                    slot_y + SLOT_SIZE / 2.0 + 8.0,
+                    28.0,
+                    WHITE,
+                );
             }
+
+            draw_rectangle_lines(slot_x, slot_y, SLOT_SIZE, SLOT_SIZE, 2.0, WHITE);
+            draw_text(&key.to_string(), slot_x + 4.0, slot_y + 16.0, 20.0, WHITE);
+            draw_text(&ability.name, slot_x, slot_y - 4.0, 16.0, WHITE);
+
+            slot_x += SLOT_SIZE + SLOT_SPACING;
         }
+
+        if let Some(gold_rush) = abilities.gold_rush {
+            let gold_rush_text = format!(
+                "Gold x{} ({:.0}s)",
+                gold_rush.multiplier,
+                gold_rush.remaining.ceil()
+            );
+            draw_text(&gold_rush_text, SLOT_SPACING, slot_y - 30.0, 28.0, GOLD);
+        }
+    }
+
+    // Bar above the ability slots, it turns red once the pickaxe is nearly broken
+    fn draw_durability(&self) {
+        const BAR_WIDTH: f32 = 200.0;
+        const BAR_HEIGHT: f32 = 14.0;
+
+        let x = 10.0;
+        let y = screen_height() - 64.0 - 10.0 - BAR_HEIGHT - 10.0;
+        let fraction = self.simulation.durability_fraction();
+        let color = if self.simulation.player.durability == 0 {
+            GRAY
+        } else if fraction < WORN_DURABILITY {
+            RED
+        } else {
+            GREEN
+        };
+
+        draw_rectangle(x, y, BAR_WIDTH, BAR_HEIGHT, Color::new(0.0, 0.0, 0.0, 0.6));
+        draw_rectangle(x, y, BAR_WIDTH * fraction, BAR_HEIGHT, color);
+        let pickaxe = self
+            .resources
+            .definitions
+            .upgrades
+            .pickaxe(self.simulation.player.current_pickaxe);
+        let text = if self.simulation.player.durability == 0 {
+            format!("{} (broken)", pickaxe.name)
+        } else {
+            pickaxe.name.clone()
+        };
+        draw_text(&text, x + BAR_WIDTH + 10.0, y + BAR_HEIGHT, 24.0, WHITE);
+    }
+
+    fn draw_biome_banner(&self) {
+        let Some((biome, timer)) = self.biome_banner else {
+            return;
+        };
+
+        // Fade out during the last second
+        let alpha = timer.min(1.0);
+        let text = format!(
+            "Entering the {}",
+            self.resources.definitions.biomes.get(biome).name
+        );
+        let size = measure_text(&text, None, 48, 1.0);
+        draw_rectangle(
+            0.0,
+            screen_height() / 4.0 - size.height - 20.0,
+            screen_width(),
+            size.height + 40.0,
+            Color::new(0.0, 0.0, 0.0, 0.5 * alpha),
+        );
+        draw_text(
+            &text,
+            (screen_width() - size.width) / 2.0,
+            screen_height() / 4.0,
+            48.0,
+            Color::new(1.0, 1.0, 1.0, alpha),
+        );
+    }
+
+    fn draw_background(&self) {
+        let camera_y = self.camera().target.y;
+        let background_y = if camera_y <= screen_height() {
+            0.0 // Keep background fixed at top when the camera is in the upper half
+        } else {
+            camera_y - screen_height() / 2. // Scroll background when the camera is lower
+        };
+
+        let [r, g, b] = self
+            .resources
+            .definitions
+            .biomes
+            .get(self.simulation.current_biome)
+            .background_tint;
+        draw_texture(
+            &self.resources.game_background_texture,
+            0.,
+            background_y,
+            Color::new(r, g, b, 1.0),
+        );
+    }
+
+    //
+    // HELPERS
+    //
+
+    // Where something is drawn, between its position before and after the last tick
+    fn interpolate(&self, previous_position: Vec2, shape: &Shape) -> Vec2 {
+        let alpha = self.tick_accumulator / TICK;
+        previous_position.lerp(vec2(shape.x, shape.y), alpha)
+    }
+
+    fn player_position(&self) -> Vec2 {
+        let player = &self.simulation.player;
+        self.interpolate(player.previous_position, &player.shape)
     }
 
-    fn draw_debug_info(player: &Player, blocks: &[Block]) {
+    fn draw_debug_info(seed: u64, player: &Player, world: &World) {
         let player_text = format!(
             "Player Position = x:{:.2} y:{:.2}",
             player.shape.x, player.shape.y
         );
         draw_text(player_text.as_str(), 10.0, 20.0, 20.0, BLACK);
 
-        let destroyed_blocks = blocks.iter().filter(|block| block.is_destroyed()).count();
-        let total_blocks = blocks.len();
         let block_text = format!(
-            "Number of blocks = Alive:{} Destroyed:{} TOTAL:{}",
-            total_blocks - destroyed_blocks,
-            destroyed_blocks,
-            total_blocks
+            "Number of blocks = {} Chunks = Loaded:{} Evicted:{}",
+            world.blocks().count(),
+            world.loaded_chunks(),
+            world.evicted_chunks()
         );
         draw_text(block_text.as_str(), 10.0, 45.0, 20.0, BLACK);
+
+        let seed_text = format!("World Seed = {}", seed);
+        draw_text(seed_text.as_str(), 10.0, 70.0, 20.0, BLACK);
     }
 
     fn init_music(&mut self) {
         if self.playing_theme == PlayingThemeAudio::OpeningTheme {
-            self.playing_theme = PlayingThemeAudio::GameTheme;
+            let biome = self.simulation.current_biome;
+            self.playing_theme = PlayingThemeAudio::Biome(biome);
             stop_sound(&self.resources.opening_theme);
-            macroquad::audio::play_sound(
-                &self.resources.game_theme,
-                macroquad::audio::PlaySoundParams {
-                    looped: true,
-                    volume: 0.5,
-                },
-            );
+            Self::play_music(self.resources.biome_music(biome));
         }
     }
 
+    // The biome tracks loop, so they have to be stopped before the game goes away
+    pub fn stop_music(&mut self) {
+        if let PlayingThemeAudio::Biome(playing) = self.playing_theme {
+            stop_sound(self.resources.biome_music(playing));
+        }
+        self.playing_theme = PlayingThemeAudio::OpeningTheme;
+    }
+
+    fn switch_music(&mut self, biome: BiomeId) {
+        let PlayingThemeAudio::Biome(playing) = self.playing_theme else {
+            return;
+        };
+
+        // Neighbouring biomes can share a track, keep it playing instead of restarting it
+        let biomes = &self.resources.definitions.biomes;
+        if biomes.get(playing).music != biomes.get(biome).music {
+            stop_sound(self.resources.biome_music(playing));
+            Self::play_music(self.resources.biome_music(biome));
+        }
+        self.playing_theme = PlayingThemeAudio::Biome(biome);
+    }
+
+    fn play_music(sound: &macroquad::audio::Sound) {
+        macroquad::audio::play_sound(
+            sound,
+            macroquad::audio::PlaySoundParams {
+                looped: true,
+                volume: 0.5,
+            },
+        );
+    }
+
     fn play_low_sound_once(sound: &macroquad::audio::Sound) {
         macroquad::audio::play_sound(
             sound,
@@ -0,0 +1,60 @@
+use macroquad::math::{vec2, Vec2};
+
+use crate::{
+    enemy_registry::{EnemyDef, EnemyId},
+    score::Score,
+    shape::Shape,
+};
+
+pub struct Enemy {
+    pub shape: Shape,
+    pub enemy_id: EnemyId,
+    pub hp: i32,
+    // Center of the tile the enemy is walking to
+    pub target: Vec2,
+    // Top-left corner before the last tick, drawing blends between both
+    pub previous_position: Vec2,
+}
+
+impl Enemy {
+    pub fn new(shape: Shape, enemy_id: EnemyId, def: &EnemyDef) -> Self {
+        let target = shape.center();
+        let previous_position = vec2(shape.x, shape.y);
+        Self {
+            shape,
+            enemy_id,
+            hp: def.hp,
+            target,
+            previous_position,
+        }
+    }
+
+    pub fn subtract_hp(&mut self, hp_to_subtract: i32) -> bool {
+        self.hp -= hp_to_subtract;
+        if self.hp <= 0 {
+            self.hp = 0;
+            return true;
+        }
+        false
+    }
+
+    // Moves the enemy towards `point` without overshooting, returns true once it got there
+    pub fn move_towards(&mut self, point: Vec2, distance: f32) -> bool {
+        let center = self.shape.center();
+        let to_point = point - center;
+        let new_center = if to_point.length() <= distance {
+            point
+        } else {
+            center + to_point.normalize() * distance
+        };
+
+        self.shape.x = new_center.x - self.shape.size.x / 2.0;
+        self.shape.y = new_center.y - self.shape.size.y / 2.0;
+        new_center == point
+    }
+
+    pub fn update_score(&self, score: &mut Score, def: &EnemyDef) {
+        score.current_score += def.score;
+        score.gold += def.gold;
+    }
+}
@@ -0,0 +1,75 @@
+use serde::Deserialize;
+
+// Index of an enemy definition inside the registry
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct EnemyId(pub usize);
+
+#[derive(Clone, Debug, Deserialize)]
+pub struct EnemyDef {
+    pub id: String,
+    pub hp: i32,
+    pub speed: f32,
+    // Health taken from the player on contact
+    pub damage: i32,
+    pub score: i32,
+    pub gold: i32,
+    // Enemies only spawn inside caves between these rows (counting from the surface)
+    pub min_row: i64,
+    #[serde(default)]
+    pub max_row: Option<i64>,
+    // Chance for every cave cell of a new row to spawn this enemy
+    pub spawn_chance: f32,
+    pub color: [f32; 3],
+}
+
+impl EnemyDef {
+    pub fn can_spawn_at(&self, row: i64) -> bool {
+        row >= self.min_row && self.max_row.is_none_or(|max_row| row <= max_row)
+    }
+}
+
+#[derive(Debug)]
+pub struct EnemyRegistry {
+    enemies: Vec<EnemyDef>,
+}
+
+impl EnemyRegistry {
+    pub fn from_json(json: &str) -> Result<Self, String> {
+        let enemies: Vec<EnemyDef> = serde_json::from_str(json).map_err(|err| err.to_string())?;
+
+        for (index, enemy) in enemies.iter().enumerate() {
+            if enemies[..index].iter().any(|other| other.id == enemy.id) {
+                return Err(format!("duplicated enemy id '{}'", enemy.id));
+            }
+            if enemy.hp <= 0 {
+                return Err(format!("enemy '{}' needs a positive hp", enemy.id));
+            }
+            if !(0.0..=1.0).contains(&enemy.spawn_chance) {
+                return Err(format!(
+                    "enemy '{}' needs a spawn_chance between 0 and 1",
+                    enemy.id
+                ));
+            }
+        }
+
+        Ok(Self { enemies })
+    }
+
+    pub fn get(&self, id: EnemyId) -> &EnemyDef {
+        &self.enemies[id.0]
+    }
+
+    pub fn find(&self, id: &str) -> Option<EnemyId> {
+        self.enemies
+            .iter()
+            .position(|enemy| enemy.id == id)
+            .map(EnemyId)
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = (EnemyId, &EnemyDef)> {
+        self.enemies
+            .iter()
+            .enumerate()
+            .map(|(index, enemy)| (EnemyId(index), enemy))
+    }
+}
@@ -0,0 +1,134 @@
+use std::collections::BTreeMap;
+
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    modes::GameMode,
+    save::{self, SaveError},
+};
+
+// Bump this whenever the high score format changes and add a step to `migrate`
+pub const HIGH_SCORES_VERSION: u32 = 2;
+const HIGH_SCORES_FILE: &str = "dwarfing_highscores.json";
+pub const MAX_ENTRIES: usize = 10;
+pub const INITIALS_LENGTH: usize = 3;
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct HighScoreEntry {
+    pub initials: String,
+    pub score: i32,
+    pub gold: i32,
+    pub deepest_row: i64,
+    pub time: f32, // Seconds the run lasted
+    pub date: u64, // Unix time in seconds
+}
+
+// Best runs of every game mode, the mode decides what "best" means
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct HighScores {
+    pub version: u32,
+    tables: BTreeMap<String, Vec<HighScoreEntry>>, // By mode id, best first
+    // Set when the high score file couldn't be loaded, so this table never writes over it
+    #[serde(skip)]
+    pub read_only: bool,
+}
+
+impl Default for HighScores {
+    fn default() -> Self {
+        Self {
+            version: HIGH_SCORES_VERSION,
+            tables: BTreeMap::new(),
+            read_only: false,
+        }
+    }
+}
+
+impl HighScores {
+    pub fn entries(&self, mode: GameMode) -> &[HighScoreEntry] {
+        self.tables.get(mode.id()).map_or(&[], |entries| entries)
+    }
+
+    // Whether a run like this would make it into the table
+    pub fn qualifies(&self, mode: GameMode, entry: &HighScoreEntry) -> bool {
+        let entries = self.entries(mode);
+        entries.len() < MAX_ENTRIES
+            || entries
+                .last()
+                .is_some_and(|last| mode.compare(entry, last).is_lt())
+    }
+
+    // Returns the position the entry got in the table, ties go below the older entries
+    pub fn insert(&mut self, mode: GameMode, entry: HighScoreEntry) -> Option<usize> {
+        if !self.qualifies(mode, &entry) {
+            return None;
+        }
+
+        let entries = self.tables.entry(mode.id().to_string()).or_default();
+        let rank = entries
+            .iter()
+            .position(|other| mode.compare(&entry, other).is_lt())
+            .unwrap_or(entries.len());
+        entries.insert(rank, entry);
+        entries.truncate(MAX_ENTRIES);
+        Some(rank)
+    }
+}
+
+// Up to three uppercase letters or digits, anything else typed is dropped
+pub fn sanitize_initials(text: &str) -> String {
+    text.chars()
+        .filter(|c| c.is_ascii_alphanumeric())
+        .map(|c| c.to_ascii_uppercase())
+        .take(INITIALS_LENGTH)
+        .collect()
+}
+
+// Returns an empty table when there is no high score file yet
+pub fn load() -> Result<HighScores, SaveError> {
+    let contents = match std::fs::read_to_string(HIGH_SCORES_FILE) {
+        Ok(contents) => contents,
+        Err(err) if err.kind() == std::io::ErrorKind::NotFound => return Ok(HighScores::default()),
+        Err(err) => return Err(err.into()),
+    };
+
+    let value: serde_json::Value = serde_json::from_str(&contents)?;
+    let version = value
+        .get("version")
+        .and_then(|version| version.as_u64())
+        .ok_or_else(|| SaveError::Invalid(String::from("missing version")))?
+        as u32;
+
+    let value = migrate(value, version)?;
+    Ok(serde_json::from_value(value)?)
+}
+
+// Does nothing for a read only table, the menu already told the player it isn't kept
+pub fn write(high_scores: &HighScores) -> Result<(), SaveError> {
+    if high_scores.read_only {
+        return Ok(());
+    }
+    save::write_atomic(HIGH_SCORES_FILE, &serde_json::to_string(high_scores)?)
+}
+
+// Upgrades older high score files to the current format one version at a time
+fn migrate(mut value: serde_json::Value, version: u32) -> Result<serde_json::Value, SaveError> {
+    match version {
+        HIGH_SCORES_VERSION => {
+            value["version"] = serde_json::json!(HIGH_SCORES_VERSION);
+            Ok(value)
+        }
+        // v2 split the table by game mode, every older run was an endless one
+        1 => {
+            let mut entries = value["entries"].take();
+            if let Some(entries) = entries.as_array_mut() {
+                for entry in entries {
+                    entry["time"] = serde_json::json!(0.0);
+                }
+            }
+            value["tables"] = serde_json::json!({ GameMode::Endless.id(): entries });
+            migrate(value, 2)
+        }
+        version if version > HIGH_SCORES_VERSION => Err(SaveError::NewerVersion(version)),
+        version => Err(SaveError::UnsupportedVersion(version)),
+    }
+}
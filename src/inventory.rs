@@ -0,0 +1,146 @@
+use crate::{
+    items::{ItemId, ItemRegistry},
+    upgrades::{StorageEffect, StorageUpgradeId, UpgradeCatalog},
+};
+
+const WEIGHT_EPSILON: f32 = 0.001; // So a multiplied weight that lands right on the limit still fits
+
+// What the player carries in the backpack and the storage upgrades that make it bigger
+pub struct Inventory {
+    counts: Vec<u32>,   // Indexed by ItemId
+    storage: Vec<bool>, // Indexed by StorageUpgradeId
+}
+
+impl Inventory {
+    pub fn new(items: &ItemRegistry, catalog: &UpgradeCatalog) -> Self {
+        Self {
+            counts: vec![0; items.iter().count()],
+            storage: vec![false; catalog.storage_upgrades().count()],
+        }
+    }
+
+    pub fn count(&self, id: ItemId) -> u32 {
+        self.counts[id.0]
+    }
+
+    pub fn set_count(&mut self, id: ItemId, count: u32) {
+        self.counts[id.0] = count;
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.counts.iter().all(|&count| count == 0)
+    }
+
+    pub fn owns_storage(&self, id: StorageUpgradeId) -> bool {
+        self.storage[id.0]
+    }
+
+    // Indexed by StorageUpgradeId, what `UpgradeCatalog::storage_blocker` wants
+    pub fn owned_storage(&self) -> &[bool] {
+        &self.storage
+    }
+
+    pub fn unlock_storage(&mut self, id: StorageUpgradeId) {
+        self.storage[id.0] = true;
+    }
+
+    pub fn max_slots(&self, catalog: &UpgradeCatalog) -> u32 {
+        catalog.backpack.slots
+            + self
+                .owned_effects(catalog)
+                .map(|effect| match effect {
+                    StorageEffect::Capacity { slots, .. } => *slots,
+                    StorageEffect::Compression { .. } => 0,
+                })
+                .sum::<u32>()
+    }
+
+    pub fn max_weight(&self, catalog: &UpgradeCatalog) -> f32 {
+        catalog.backpack.max_weight
+            + self
+                .owned_effects(catalog)
+                .map(|effect| match effect {
+                    StorageEffect::Capacity { weight, .. } => *weight,
+                    StorageEffect::Compression { .. } => 0.0,
+                })
+                .sum::<f32>()
+    }
+
+    // Every item stack takes its own slots, a slot holds `stack` units of a single item
+    pub fn slots_used(&self, items: &ItemRegistry) -> u32 {
+        items
+            .iter()
+            .map(|(item_id, item)| self.count(item_id).div_ceil(item.stack))
+            .sum()
+    }
+
+    // Total weight with the compression upgrades applied
+    pub fn weight(&self, items: &ItemRegistry, catalog: &UpgradeCatalog) -> f32 {
+        let weight: f32 = items
+            .iter()
+            .map(|(item_id, item)| self.count(item_id) as f32 * item.weight)
+            .sum();
+        weight * self.weight_multiplier(catalog)
+    }
+
+    // Gold the whole backpack is worth at the shop, before any bonus
+    pub fn value(&self, items: &ItemRegistry) -> i32 {
+        items
+            .iter()
+            .map(|(item_id, item)| self.count(item_id) as i32 * item.price)
+            .sum()
+    }
+
+    // Returns false, leaving the backpack as it was, if they don't all fit
+    pub fn add(
+        &mut self,
+        id: ItemId,
+        count: u32,
+        items: &ItemRegistry,
+        catalog: &UpgradeCatalog,
+    ) -> bool {
+        self.counts[id.0] += count;
+        let fits = self.slots_used(items) <= self.max_slots(catalog)
+            && self.weight(items, catalog) <= self.max_weight(catalog) + WEIGHT_EPSILON;
+        if !fits {
+            self.counts[id.0] -= count;
+        }
+        fits
+    }
+
+    // Returns false, leaving the backpack as it was, if there aren't enough of them
+    pub fn remove(&mut self, id: ItemId, count: u32) -> bool {
+        if self.counts[id.0] < count {
+            return false;
+        }
+        self.counts[id.0] -= count;
+        true
+    }
+
+    pub fn clear(&mut self) {
+        self.counts.fill(0);
+    }
+
+    //
+    // HELPERS
+    //
+
+    fn owned_effects<'a>(
+        &'a self,
+        catalog: &'a UpgradeCatalog,
+    ) -> impl Iterator<Item = &'a StorageEffect> {
+        catalog
+            .storage_upgrades()
+            .filter(|(upgrade_id, _)| self.owns_storage(*upgrade_id))
+            .map(|(_, upgrade)| &upgrade.effect)
+    }
+
+    fn weight_multiplier(&self, catalog: &UpgradeCatalog) -> f32 {
+        self.owned_effects(catalog)
+            .map(|effect| match effect {
+                StorageEffect::Compression { weight_multiplier } => *weight_multiplier,
+                StorageEffect::Capacity { .. } => 1.0,
+            })
+            .product()
+    }
+}
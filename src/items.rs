@@ -0,0 +1,74 @@
+use serde::Deserialize;
+
+use crate::block_registry::{BlockId, BlockRegistry};
+
+// Index of an item definition inside the registry
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct ItemId(pub usize);
+
+#[derive(Clone, Debug, Deserialize)]
+pub struct ItemDef {
+    pub id: String,
+    pub name: String,
+    pub weight: f32, // Per unit, in kg
+    pub price: i32,  // Gold for every unit sold at the base
+    pub stack: u32,  // Units that fit in one backpack slot
+}
+
+// Things that go in the backpack, loaded from items.json
+#[derive(Debug)]
+pub struct ItemRegistry {
+    items: Vec<ItemDef>,
+    drops: Vec<Option<ItemId>>, // Indexed by BlockId
+}
+
+impl ItemRegistry {
+    pub fn from_json(json: &str, blocks: &BlockRegistry) -> Result<Self, String> {
+        let items: Vec<ItemDef> = serde_json::from_str(json).map_err(|err| err.to_string())?;
+
+        for (index, item) in items.iter().enumerate() {
+            if items[..index].iter().any(|other| other.id == item.id) {
+                return Err(format!("duplicated item id '{}'", item.id));
+            }
+            if item.weight < 0.0 {
+                return Err(format!("item '{}' can't have a negative weight", item.id));
+            }
+            if item.stack == 0 {
+                return Err(format!("item '{}' needs a stack of at least one", item.id));
+            }
+        }
+
+        let find_item = |id: &str| items.iter().position(|item| item.id == id).map(ItemId);
+        let drops = blocks
+            .iter()
+            .map(|(_, block)| match &block.drop {
+                Some(drop) => find_item(drop).map(Some).ok_or_else(|| {
+                    format!("block '{}' drops the unknown item '{}'", block.id, drop)
+                }),
+                None => Ok(None),
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+
+        Ok(Self { items, drops })
+    }
+
+    pub fn get(&self, id: ItemId) -> &ItemDef {
+        &self.items[id.0]
+    }
+
+    pub fn find(&self, id: &str) -> Option<ItemId> {
+        self.items.iter().position(|item| item.id == id).map(ItemId)
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = (ItemId, &ItemDef)> {
+        self.items
+            .iter()
+            .enumerate()
+            .map(|(index, item)| (ItemId(index), item))
+    }
+
+    // The item a block turns into when it's broken, blocks without one pay their gold right away
+    pub fn drop_of(&self, block: BlockId) -> Option<ItemId> {
+        self.drops[block.0]
+    }
+}
@@ -1,16 +1,49 @@
+use achievements::AchievementRegistry;
+use daily::DailyResult;
+use highscores::{HighScoreEntry, HighScores};
 use macroquad::{
     audio::Sound,
     prelude::*,
     ui::{hash, root_ui},
 };
+use modes::GameMode;
+use profile::Profile;
 use resources::Resources;
+use save::{SaveError, SaveGame};
+use score::Score;
+use upgrades::UpgradeCatalog;
 
+mod abilities;
+mod achievements;
+mod auto_miners;
+mod biome;
 mod block;
+mod block_registry;
+mod crafting;
+mod daily;
+mod definitions;
 mod dwarfing;
+mod enemy;
+mod enemy_registry;
+mod highscores;
+mod inventory;
+mod items;
+mod modes;
+mod noise;
 mod player;
+mod profile;
+mod recipes;
+mod replay;
 mod resources;
+mod rng;
+mod save;
 mod score;
 mod shape;
+mod simulation;
+mod stats;
+mod terrain;
+mod upgrades;
+mod world;
 
 const WINDOW_SIZE_X: i32 = 1056;
 const WINDOW_SIZE_Y: i32 = 800;
@@ -18,6 +51,50 @@ const WINDOW_SIZE_Y: i32 = 800;
 enum GameState {
     Menu,
     Playing,
+    GameOver(GameOverScreen),
+    PrestigeShop,
+    Achievements,
+    Stats(String),        // Result of the last export
+    HighScores(GameMode), // Table being shown
+    ModeSelect,
+    DailyHistory(String),            // Result of the last copy
+    Replay(Box<dwarfing::Dwarfing>), // Kept apart from `game` so nothing gets saved while watching
+}
+
+enum MenuAction {
+    None,
+    ChooseMode,
+    Continue,
+    PrestigeShop,
+    Achievements,
+    Stats,
+    HighScores,
+    Quit,
+}
+
+enum ModeSelectAction {
+    None,
+    Start(GameMode),
+    DailyHistory,
+    Back,
+}
+
+enum GameOverAction {
+    None,
+    PlayAgain,
+    Menu,
+}
+
+// What the game over screen needs to remember between frames
+struct GameOverScreen {
+    score: Score, // Final score of the run
+    mode: GameMode,
+    entry: HighScoreEntry, // The initials get typed straight into it
+    counts_for_high_scores: bool,
+    rank: Option<usize>, // Position in the high score table once submitted
+    submitted: bool,
+    daily: Option<DailyResult>, // Daily challenge runs can be shared instead
+    status: String,
 }
 
 fn window_conf() -> Conf {
@@ -31,8 +108,17 @@ fn window_conf() -> Conf {
     }
 }
 
-fn menu_ui(button_sound: &Sound) -> GameState {
-    let mut state = GameState::Menu;
+fn menu_ui(
+    button_sound: &Sound,
+    seed_input: &mut String,
+    save_state: &Result<Option<SaveGame>, SaveError>,
+    profile_error: Option<&SaveError>,
+    high_scores_error: Option<&SaveError>,
+    profile: &Profile,
+) -> MenuAction {
+    let mut action = MenuAction::None;
+    let can_continue = matches!(save_state, Ok(Some(_)));
+    let has_prestiged = profile.prestige_level > 0;
 
     let window_width = screen_width();
     let window_height = screen_height();
@@ -41,10 +127,18 @@ fn menu_ui(button_sound: &Sound) -> GameState {
     let button_width = 200.0;
     let button_height = 50.0;
     let label_height = 40.0;
-    let vertical_spacing = 50.0;
+    let input_height = 30.0;
+    let vertical_spacing = 30.0;
 
     // Calculate total height of all elements
-    let total_height = label_height + (button_height * 2.0) + (vertical_spacing * 2.0);
+    let mut total_height =
+        label_height + input_height + (button_height * 5.0) + (vertical_spacing * 6.0);
+    if can_continue {
+        total_height += button_height + vertical_spacing;
+    }
+    if has_prestiged {
+        total_height += button_height + vertical_spacing;
+    }
 
     // Calculate starting Y position to center everything vertically
     let start_y = (window_height - total_height) / 2.0;
@@ -54,31 +148,589 @@ fn menu_ui(button_sound: &Sound) -> GameState {
         vec2(0.0, 0.0),
         vec2(window_width, window_height),
         |ui| {
+            let mut next_y = start_y;
+            if can_continue {
+                let continue_button_x = (window_width - button_width) / 2.0;
+                if ui.button(vec2(continue_button_x, next_y), "Continue") {
+                    macroquad::audio::play_sound_once(button_sound);
+                    action = MenuAction::Continue;
+                }
+                next_y += button_height + vertical_spacing;
+            }
+
+            // Leave it empty to get a random world
+            let seed_x = (window_width - button_width) / 2.0;
+            let seed_y = next_y + label_height + vertical_spacing;
+            ui.label(vec2(seed_x, seed_y - label_height), "World Seed");
+            macroquad::ui::widgets::InputText::new(hash!())
+                .position(vec2(seed_x, seed_y))
+                .size(vec2(button_width, input_height))
+                .ui(ui, seed_input);
+
             let play_button_x = (window_width - button_width) / 2.0;
-            let play_button_y = start_y + label_height + vertical_spacing;
+            let play_button_y = seed_y + input_height + vertical_spacing;
             if ui.button(vec2(play_button_x, play_button_y), "Play") {
                 macroquad::audio::play_sound_once(button_sound);
-                state = GameState::Playing;
+                action = MenuAction::ChooseMode;
+            }
+
+            let mut quit_button_y = play_button_y + button_height + vertical_spacing;
+            if has_prestiged {
+                let prestige_button_x = (window_width - button_width) / 2.0;
+                if ui.button(vec2(prestige_button_x, quit_button_y), "Prestige Shop") {
+                    macroquad::audio::play_sound_once(button_sound);
+                    action = MenuAction::PrestigeShop;
+                }
+                quit_button_y += button_height + vertical_spacing;
+
+                ui.label(
+                    vec2(20.0, 20.0),
+                    &format!("Prestige Level {}", profile.prestige_level),
+                );
+                ui.label(
+                    vec2(20.0, 20.0 + label_height),
+                    &format!("Runes: {}", profile.runes),
+                );
+            }
+
+            let achievements_button_x = (window_width - button_width) / 2.0;
+            if ui.button(vec2(achievements_button_x, quit_button_y), "Achievements") {
+                macroquad::audio::play_sound_once(button_sound);
+                action = MenuAction::Achievements;
+            }
+            quit_button_y += button_height + vertical_spacing;
+
+            let stats_button_x = (window_width - button_width) / 2.0;
+            if ui.button(vec2(stats_button_x, quit_button_y), "Statistics") {
+                macroquad::audio::play_sound_once(button_sound);
+                action = MenuAction::Stats;
             }
+            quit_button_y += button_height + vertical_spacing;
+
+            let high_scores_button_x = (window_width - button_width) / 2.0;
+            if ui.button(vec2(high_scores_button_x, quit_button_y), "High Scores") {
+                macroquad::audio::play_sound_once(button_sound);
+                action = MenuAction::HighScores;
+            }
+            quit_button_y += button_height + vertical_spacing;
 
             let quit_button_x = (window_width - button_width) / 2.0;
-            let quit_button_y = play_button_y + button_height + vertical_spacing;
             if ui.button(vec2(quit_button_x, quit_button_y), "Quit") {
                 macroquad::audio::play_sound_once(button_sound);
-                std::process::exit(0);
+                action = MenuAction::Quit;
+            }
+
+            let mut error_y = window_height - label_height - 20.0;
+            if let Err(err) = save_state {
+                let error_text = format!("Can't continue: {}", err);
+                ui.label(vec2(20.0, error_y), error_text.as_str());
+                error_y -= label_height;
+            }
+            if let Some(err) = profile_error {
+                let error_text = format!("Can't load the profile, progress won't be kept: {}", err);
+                ui.label(vec2(20.0, error_y), error_text.as_str());
+                error_y -= label_height;
+            }
+            if let Some(err) = high_scores_error {
+                let error_text = format!(
+                    "Can't load the high scores, new ones won't be kept: {}",
+                    err
+                );
+                ui.label(vec2(20.0, error_y), error_text.as_str());
             }
         },
     );
-    state
+    action
 }
 
-#[macroquad::main(window_conf)]
-async fn main() {
-    set_pc_assets_folder("assets");
+// Final score and, if it made the table, the initials prompt
+fn game_over_ui(
+    button_sound: &Sound,
+    screen: &mut GameOverScreen,
+    high_scores: &mut HighScores,
+) -> GameOverAction {
+    let mut action = GameOverAction::None;
+    let mut submit = false;
 
-    let resources = Resources::new().await;
+    let window_width = screen_width();
+    let window_height = screen_height();
 
-    let button_sound = resources.start_button_sound.clone();
+    let button_width = 200.0;
+    let label_height = 40.0;
+    let input_height = 30.0;
+    let vertical_spacing = 20.0;
+
+    let score = &screen.score;
+    let mut lines = vec![
+        format!("Game Over ({})", screen.mode.name()),
+        format!("Score = {}", score.current_score),
+        format!("Destroyed Blocks = {}", score.blocks_destroyed),
+        format!("Gold = {}", score.gold),
+    ];
+    let can_submit = !screen.submitted
+        && screen.counts_for_high_scores
+        && high_scores.qualifies(screen.mode, &screen.entry);
+    if screen.daily.is_some() {
+        lines.push(String::from("Saved in the Daily Dig history"));
+    } else if screen.submitted {
+        if let Some(rank) = screen.rank {
+            lines.push(format!("You placed #{} in the high scores!", rank + 1));
+        }
+    } else if !screen.counts_for_high_scores {
+        lines.push(String::from(
+            "The run wasn't finished, so it can't be a high score",
+        ));
+    } else if can_submit {
+        lines.push(String::from("New high score! Enter your initials"));
+    } else {
+        lines.push(String::from("No high score this time"));
+    }
+
+    let start_y = window_height / 3.0;
+
+    root_ui().window(
+        hash!(),
+        vec2(0.0, 0.0),
+        vec2(window_width, window_height),
+        |ui| {
+            let mut next_y = start_y;
+            for line in &lines {
+                let line_x = (window_width - measure_text(line, None, 28, 1.0).width) / 2.0;
+                ui.label(vec2(line_x, next_y), line);
+                next_y += label_height;
+            }
+
+            if can_submit {
+                let initials_x = (window_width - button_width) / 2.0;
+                macroquad::ui::widgets::InputText::new(hash!())
+                    .position(vec2(initials_x, next_y))
+                    .size(vec2(button_width, input_height))
+                    .ui(ui, &mut screen.entry.initials);
+                next_y += input_height + vertical_spacing;
+
+                if ui.button(vec2(initials_x, next_y), "Submit") {
+                    macroquad::audio::play_sound_once(button_sound);
+                    submit = true;
+                }
+                next_y += label_height;
+            }
+
+            if let Some(daily) = &screen.daily {
+                let share_button_x = (window_width - button_width) / 2.0;
+                if ui.button(vec2(share_button_x, next_y), "Copy Share Text") {
+                    macroquad::audio::play_sound_once(button_sound);
+                    macroquad::miniquad::window::clipboard_set(&daily.share_text());
+                    screen.status = String::from("Copied to the clipboard");
+                }
+                ui.label(vec2(share_button_x + button_width, next_y), &screen.status);
+                next_y += label_height;
+            }
+
+            let play_again_button_x = (window_width - button_width) / 2.0;
+            if ui.button(
+                vec2(play_again_button_x, next_y + vertical_spacing),
+                "Play Again",
+            ) {
+                macroquad::audio::play_sound_once(button_sound);
+                action = GameOverAction::PlayAgain;
+            }
+            next_y += label_height + vertical_spacing;
+
+            let menu_button_x = (window_width - button_width) / 2.0;
+            if ui.button(vec2(menu_button_x, next_y + vertical_spacing), "Menu") {
+                macroquad::audio::play_sound_once(button_sound);
+                action = GameOverAction::Menu;
+            }
+        },
+    );
+    screen.entry.initials = highscores::sanitize_initials(&screen.entry.initials);
+
+    if submit && !screen.entry.initials.is_empty() {
+        screen.submitted = true;
+        screen.rank = high_scores.insert(screen.mode, screen.entry.clone());
+        if let Err(err) = highscores::write(high_scores) {
+            eprintln!("Could not save the high scores: {}", err);
+        }
+    }
+    action
+}
+
+// One table per game mode, picked with the buttons at the top. Returns true when the player wants to go back to the menu
+fn high_scores_ui(button_sound: &Sound, high_scores: &HighScores, mode: &mut GameMode) -> bool {
+    let mut back_to_menu = false;
+
+    let window_width = screen_width();
+    let window_height = screen_height();
+
+    let row_height = 40.0;
+    let tab_width = 180.0;
+    let start_y = 110.0;
+
+    let entries = high_scores.entries(*mode);
+
+    root_ui().window(
+        hash!(),
+        vec2(0.0, 0.0),
+        vec2(window_width, window_height),
+        |ui| {
+            for (index, tab) in GameMode::ALL.into_iter().enumerate() {
+                let tab_x = 40.0 + index as f32 * tab_width;
+                if ui.button(vec2(tab_x, 30.0), tab.name()) {
+                    macroquad::audio::play_sound_once(button_sound);
+                    *mode = tab;
+                }
+            }
+
+            ui.label(vec2(40.0, start_y), "#");
+            ui.label(vec2(100.0, start_y), "Name");
+            ui.label(vec2(220.0, start_y), mode.name());
+            ui.label(vec2(400.0, start_y), "Row");
+            ui.label(vec2(520.0, start_y), "Date");
+            for (index, entry) in entries.iter().enumerate() {
+                let row_y = start_y + (index + 1) as f32 * row_height;
+                ui.label(vec2(40.0, row_y), &(index + 1).to_string());
+                ui.label(vec2(100.0, row_y), &entry.initials);
+                ui.label(vec2(220.0, row_y), &mode.format_result(entry));
+                ui.label(vec2(400.0, row_y), &entry.deepest_row.to_string());
+                ui.label(vec2(520.0, row_y), &stats::format_date(entry.date));
+            }
+            if entries.is_empty() {
+                ui.label(vec2(40.0, start_y + row_height), "No runs recorded yet");
+            }
+
+            if ui.button(vec2(40.0, window_height - 100.0), "Back") {
+                macroquad::audio::play_sound_once(button_sound);
+                back_to_menu = true;
+            }
+        },
+    );
+    back_to_menu
+}
+
+// Every game mode with what it's about
+fn mode_select_ui(button_sound: &Sound) -> ModeSelectAction {
+    let mut action = ModeSelectAction::None;
+
+    let window_width = screen_width();
+    let window_height = screen_height();
+
+    let row_height = 80.0;
+    let start_y = 120.0;
+
+    root_ui().window(
+        hash!(),
+        vec2(0.0, 0.0),
+        vec2(window_width, window_height),
+        |ui| {
+            ui.label(vec2(40.0, 50.0), "Choose a game mode");
+            let daily = GameMode::Daily(daily::today());
+            let modes = GameMode::ALL.into_iter().chain([daily]);
+            for (index, mode) in modes.enumerate() {
+                let row_y = start_y + index as f32 * row_height;
+                if ui.button(vec2(40.0, row_y), mode.name()) {
+                    macroquad::audio::play_sound_once(button_sound);
+                    action = ModeSelectAction::Start(mode);
+                }
+                ui.label(vec2(260.0, row_y), &mode.description());
+            }
+
+            if ui.button(vec2(40.0, window_height - 100.0), "Back") {
+                macroquad::audio::play_sound_once(button_sound);
+                action = ModeSelectAction::Back;
+            }
+            if ui.button(vec2(140.0, window_height - 100.0), "Daily History") {
+                macroquad::audio::play_sound_once(button_sound);
+                action = ModeSelectAction::DailyHistory;
+            }
+        },
+    );
+    action
+}
+
+// Every achievement with the unlocked ones marked, returns true when the player wants to go back to the menu
+fn achievements_ui(
+    button_sound: &Sound,
+    profile: &Profile,
+    registry: &AchievementRegistry,
+) -> bool {
+    let mut back_to_menu = false;
+
+    let window_width = screen_width();
+    let window_height = screen_height();
+
+    let row_height = 45.0;
+    let start_y = 40.0;
+
+    root_ui().window(
+        hash!(),
+        vec2(0.0, 0.0),
+        vec2(window_width, window_height),
+        |ui| {
+            for (index, achievement) in registry.iter().enumerate() {
+                let row_y = start_y + index as f32 * row_height;
+                let mark = if profile.achievements.contains(&achievement.id) {
+                    "[X]"
+                } else {
+                    "[ ]"
+                };
+                ui.label(vec2(40.0, row_y), mark);
+                ui.label(vec2(100.0, row_y), &achievement.name);
+                ui.label(vec2(380.0, row_y), &achievement.description);
+            }
+
+            if ui.button(vec2(40.0, window_height - 100.0), "Back") {
+                macroquad::audio::play_sound_once(button_sound);
+                back_to_menu = true;
+            }
+        },
+    );
+    back_to_menu
+}
+
+// Best run of every daily challenge played, newest first. Returns true when the player wants to go back
+fn daily_history_ui(button_sound: &Sound, profile: &Profile, status: &mut String) -> bool {
+    let mut back = false;
+
+    let window_width = screen_width();
+    let window_height = screen_height();
+
+    let row_height = 40.0;
+    let start_y = 40.0;
+    let max_days = 14;
+
+    root_ui().window(
+        hash!(),
+        vec2(0.0, 0.0),
+        vec2(window_width, window_height),
+        |ui| {
+            ui.label(vec2(40.0, start_y), "Day");
+            ui.label(vec2(200.0, start_y), "Modifier");
+            ui.label(vec2(380.0, start_y), "Score");
+            ui.label(vec2(500.0, start_y), "Row");
+            ui.label(vec2(600.0, start_y), "Attempts");
+            for (index, result) in profile
+                .daily_history
+                .iter()
+                .rev()
+                .take(max_days)
+                .enumerate()
+            {
+                let row_y = start_y + (index + 1) as f32 * row_height;
+                ui.label(vec2(40.0, row_y), &daily::format_day(result.day));
+                ui.label(vec2(200.0, row_y), result.modifier.name());
+                ui.label(vec2(380.0, row_y), &result.score.to_string());
+                ui.label(vec2(500.0, row_y), &result.deepest_row.to_string());
+                ui.label(vec2(600.0, row_y), &result.attempts.to_string());
+                if ui.button(vec2(740.0, row_y), "Copy") {
+                    macroquad::audio::play_sound_once(button_sound);
+                    macroquad::miniquad::window::clipboard_set(&result.share_text());
+                    *status = format!("Copied the {} run", daily::format_day(result.day));
+                }
+            }
+            if profile.daily_history.is_empty() {
+                ui.label(
+                    vec2(40.0, start_y + row_height),
+                    "No daily challenges played yet",
+                );
+            }
+
+            if ui.button(vec2(40.0, window_height - 100.0), "Back") {
+                macroquad::audio::play_sound_once(button_sound);
+                back = true;
+            }
+            ui.label(vec2(140.0, window_height - 100.0), status);
+        },
+    );
+    back
+}
+
+// Lifetime totals, per block numbers and the latest runs, returns true when the player wants to go back to the menu
+fn stats_ui(button_sound: &Sound, profile: &Profile, status: &mut String) -> bool {
+    let mut back_to_menu = false;
+    let stats = &profile.stats;
+
+    let window_width = screen_width();
+    let window_height = screen_height();
+
+    let row_height = 28.0;
+    let max_runs = 12;
+
+    let totals = [
+        format!("Deepest Row = {}", stats.deepest_row),
+        format!(
+            "Time Played = {}",
+            stats::format_duration(stats.seconds_played)
+        ),
+        format!("Gold Earned = {}", stats.gold_earned),
+        format!("Gold Spent = {}", stats.gold_spent),
+        format!("Blocks Destroyed = {}", stats.blocks_destroyed()),
+        format!("Enemies Killed = {}", stats.enemies_killed),
+        format!("Runs = {}", stats.runs.len()),
+    ];
+
+    root_ui().window(
+        hash!(),
+        vec2(0.0, 0.0),
+        vec2(window_width, window_height),
+        |ui| {
+            let mut next_y = 30.0;
+            for line in &totals {
+                ui.label(vec2(40.0, next_y), line);
+                next_y += row_height;
+            }
+
+            next_y += row_height;
+            ui.label(vec2(40.0, next_y), "Block");
+            ui.label(vec2(180.0, next_y), "Destroyed");
+            ui.label(vec2(290.0, next_y), "Clicks");
+            ui.label(vec2(380.0, next_y), "Damage");
+            for (id, block) in &stats.blocks {
+                next_y += row_height;
+                ui.label(vec2(40.0, next_y), id);
+                ui.label(vec2(180.0, next_y), &block.destroyed.to_string());
+                ui.label(vec2(290.0, next_y), &block.clicks.to_string());
+                ui.label(vec2(380.0, next_y), &block.damage.to_string());
+            }
+
+            // Newest runs first
+            let mut run_y = 30.0;
+            ui.label(vec2(520.0, run_y), "Date");
+            ui.label(vec2(690.0, run_y), "Duration");
+            ui.label(vec2(820.0, run_y), "Score");
+            ui.label(vec2(910.0, run_y), "Row");
+            for run in stats.runs.iter().rev().take(max_runs) {
+                run_y += row_height;
+                let date = if run.started_at > 0 {
+                    stats::format_date(run.started_at)
+                } else {
+                    String::from("Unknown")
+                };
+                ui.label(vec2(520.0, run_y), &date);
+                ui.label(vec2(690.0, run_y), &stats::format_duration(run.duration));
+                ui.label(vec2(820.0, run_y), &run.score.to_string());
+                ui.label(vec2(910.0, run_y), &run.deepest_row.to_string());
+            }
+
+            let buttons_y = window_height - 100.0;
+            if ui.button(vec2(40.0, buttons_y), "Back") {
+                macroquad::audio::play_sound_once(button_sound);
+                back_to_menu = true;
+            }
+            if ui.button(vec2(140.0, buttons_y), "Export CSV") {
+                macroquad::audio::play_sound_once(button_sound);
+                *status = match stats.export_csv() {
+                    Ok(files) => format!("Exported to {}", files),
+                    Err(err) => format!("Export failed: {}", err),
+                };
+            }
+            if ui.button(vec2(300.0, buttons_y), "Export JSON") {
+                macroquad::audio::play_sound_once(button_sound);
+                *status = match stats.export_json() {
+                    Ok(file) => format!("Exported to {}", file),
+                    Err(err) => format!("Export failed: {}", err),
+                };
+            }
+            ui.label(vec2(470.0, buttons_y), status);
+        },
+    );
+    back_to_menu
+}
+
+// Permanent upgrades bought with runes, returns true when the player wants to go back to the menu
+fn prestige_shop_ui(button_sound: &Sound, profile: &mut Profile, catalog: &UpgradeCatalog) -> bool {
+    let mut back_to_menu = false;
+    let mut bought = None;
+
+    let window_width = screen_width();
+    let window_height = screen_height();
+
+    let row_height = 60.0;
+    let start_y = 120.0;
+
+    root_ui().window(
+        hash!(),
+        vec2(0.0, 0.0),
+        vec2(window_width, window_height),
+        |ui| {
+            ui.label(vec2(40.0, 40.0), &format!("Runes: {}", profile.runes));
+
+            let Some(prestige) = &catalog.prestige else {
+                return;
+            };
+            for (index, (upgrade_id, upgrade)) in prestige.upgrades().enumerate() {
+                let row_y = start_y + index as f32 * row_height;
+                let level = profile.upgrade_level(&upgrade.id);
+                ui.label(vec2(40.0, row_y), &upgrade.name);
+                ui.label(
+                    vec2(320.0, row_y),
+                    &format!("Level {}/{}", level, upgrade.max_level),
+                );
+                match profile.upgrade_blocker(catalog, upgrade_id) {
+                    Some(reason) => ui.label(vec2(520.0, row_y), &reason),
+                    None => {
+                        let price_text = format!("Buy ({} runes)", upgrade.price(level));
+                        if ui.button(vec2(520.0, row_y - 10.0), price_text.as_str()) {
+                            bought = Some(upgrade_id);
+                        }
+                    }
+                }
+            }
+
+            if ui.button(vec2(40.0, window_height - 100.0), "Back") {
+                macroquad::audio::play_sound_once(button_sound);
+                back_to_menu = true;
+            }
+        },
+    );
+
+    if let Some(upgrade_id) = bought {
+        macroquad::audio::play_sound_once(button_sound);
+        profile.buy_upgrade(catalog, upgrade_id);
+        if let Err(err) = profile::write(profile) {
+            eprintln!("Could not save the profile: {}", err);
+        }
+    }
+    back_to_menu
+}
+
+// A fresh run on the typed seed, or a random one when it's empty
+fn new_game(
+    resources: &Resources,
+    seed_input: &str,
+    profile: &Profile,
+    mode: GameMode,
+) -> dwarfing::Dwarfing {
+    // Everyone plays the same world in the daily challenge
+    let seed = match mode {
+        GameMode::Daily(day) => daily::seed(day),
+        _ => rng::seed_from_text(seed_input).unwrap_or_else(rng::random_seed),
+    };
+    dwarfing::Dwarfing::init(resources.clone(), seed, profile.clone(), mode)
+}
+
+// Value after a command line flag, or the default replay file when it's missing
+fn replay_arg(args: &[String], flag: &str) -> Option<String> {
+    let index = args.iter().position(|arg| arg == flag)?;
+    match args.get(index + 1) {
+        Some(file) if !file.starts_with("--") => Some(file.clone()),
+        _ => Some(String::from(replay::REPLAY_FILE)),
+    }
+}
+
+fn save_and_quit(game: &Option<dwarfing::Dwarfing>) -> ! {
+    if let Some(game) = game {
+        if let Err(err) = game.save() {
+            eprintln!("Could not save the game: {}", err);
+        }
+        if let Err(err) = game.write_replay() {
+            eprintln!("Could not save the replay: {}", err);
+        }
+    }
+    std::process::exit(0);
+}
+
+// Menu music, the games stop it when they start their biome tracks
+fn play_opening_theme(resources: &Resources) {
     macroquad::audio::play_sound(
         &resources.opening_theme,
         macroquad::audio::PlaySoundParams {
@@ -86,21 +738,214 @@ async fn main() {
             volume: 1.,
         },
     );
+}
+
+#[macroquad::main(window_conf)]
+async fn main() {
+    set_pc_assets_folder("assets");
+    prevent_quit(); // So we get a chance to save before closing
+
+    let resources = Resources::new().await;
+
+    let button_sound = resources.start_button_sound.clone();
+    play_opening_theme(&resources);
     resources.clone().build_ui(); // TODO: Can I avoid cloning here?
 
+    // `--verify-replay [file]` checks a replay without showing it, `--replay [file]` plays it back
+    let args: Vec<String> = std::env::args().collect();
+    if let Some(file) = replay_arg(&args, "--verify-replay") {
+        let result = replay::load(&file)
+            .map_err(|err| err.to_string())
+            .and_then(|replay| replay.verify(resources.definitions.clone()));
+        match result {
+            Ok(hash) => {
+                println!("Replay verified, final state {:016x}", hash);
+                std::process::exit(0);
+            }
+            Err(err) => {
+                eprintln!("Replay verification failed: {}", err);
+                std::process::exit(1);
+            }
+        }
+    }
+
     let mut game_state = GameState::Menu;
-    let mut game = dwarfing::Dwarfing::init(resources);
+    if let Some(file) = replay_arg(&args, "--replay") {
+        match replay::load(&file) {
+            Ok(replay) => {
+                game_state = GameState::Replay(Box::new(dwarfing::Dwarfing::from_replay(
+                    resources.clone(),
+                    replay,
+                )))
+            }
+            Err(err) => eprintln!("Could not load the replay: {}", err),
+        }
+    }
+    let mut game: Option<dwarfing::Dwarfing> = None;
+    let mut seed_input = String::new();
+    let mut save_state = save::load();
+    // Whatever is in a profile that can't be loaded is left alone, the run goes on with an empty one
+    let (mut profile, profile_error) = match profile::load() {
+        Ok(profile) => (profile, None),
+        Err(err) => (
+            Profile {
+                read_only: true,
+                ..Profile::default()
+            },
+            Some(err),
+        ),
+    };
+    let (mut high_scores, high_scores_error) = match highscores::load() {
+        Ok(high_scores) => (high_scores, None),
+        Err(err) => {
+            let mut high_scores = HighScores::default();
+            high_scores.read_only = true;
+            (high_scores, Some(err))
+        }
+    };
 
     loop {
+        if is_quit_requested() {
+            save_and_quit(&game);
+        }
+
         clear_background(LIGHTGRAY);
 
         match game_state {
-            GameState::Menu => {
-                game_state = menu_ui(&button_sound);
-            }
+            GameState::Menu => match menu_ui(
+                &button_sound,
+                &mut seed_input,
+                &save_state,
+                profile_error.as_ref(),
+                high_scores_error.as_ref(),
+                &profile,
+            ) {
+                MenuAction::ChooseMode => game_state = GameState::ModeSelect,
+                MenuAction::Continue => {
+                    if let Ok(Some(save)) = &save_state {
+                        match dwarfing::Dwarfing::from_save(
+                            resources.clone(),
+                            save,
+                            profile.clone(),
+                        ) {
+                            Ok(loaded_game) => {
+                                game = Some(loaded_game);
+                                game_state = GameState::Playing;
+                            }
+                            Err(err) => save_state = Err(err),
+                        }
+                    }
+                }
+                MenuAction::PrestigeShop => game_state = GameState::PrestigeShop,
+                MenuAction::Achievements => game_state = GameState::Achievements,
+                MenuAction::Stats => game_state = GameState::Stats(String::new()),
+                MenuAction::HighScores => game_state = GameState::HighScores(GameMode::Endless),
+                MenuAction::Quit => save_and_quit(&game),
+                MenuAction::None => {}
+            },
             GameState::Playing => {
-                game.update();
-                game.draw();
+                if let Some(current_game) = game.as_mut() {
+                    current_game.update();
+                    current_game.draw();
+
+                    // A finished run can't be continued, so the save goes away with it
+                    let claimed_prestige = current_game.claimed_prestige();
+                    if current_game.is_game_over() || claimed_prestige.is_some() {
+                        profile = current_game.profile().clone();
+                        profile.stats.add_run(current_game.run_record());
+                        if let Err(err) = current_game.write_replay() {
+                            eprintln!("Could not save the replay: {}", err);
+                        }
+                        let daily = current_game.daily_result();
+                        if let Some(result) = &daily {
+                            profile.record_daily(result.clone());
+                        }
+                        if let Err(err) = profile::write(&profile) {
+                            eprintln!("Could not save the profile: {}", err);
+                        }
+                        game_state = match claimed_prestige {
+                            Some(runes) => {
+                                profile.add_prestige(runes);
+                                if let Err(err) = profile::write(&profile) {
+                                    eprintln!("Could not save the profile: {}", err);
+                                }
+                                GameState::PrestigeShop
+                            }
+                            None => GameState::GameOver(GameOverScreen {
+                                score: current_game.score().clone(),
+                                mode: current_game.mode(),
+                                entry: current_game.high_score_entry(),
+                                counts_for_high_scores: current_game.counts_for_high_scores(),
+                                rank: None,
+                                submitted: false,
+                                daily,
+                                status: String::new(),
+                            }),
+                        };
+                        current_game.stop_music();
+                        play_opening_theme(&resources);
+                        game = None;
+                        if let Err(err) = save::delete() {
+                            eprintln!("Could not delete the save file: {}", err);
+                        }
+                        save_state = Ok(None);
+                    }
+                }
+            }
+            GameState::PrestigeShop => {
+                if prestige_shop_ui(&button_sound, &mut profile, &resources.definitions.upgrades) {
+                    game_state = GameState::Menu;
+                }
+            }
+            GameState::Achievements => {
+                if achievements_ui(&button_sound, &profile, &resources.definitions.achievements) {
+                    game_state = GameState::Menu;
+                }
+            }
+            GameState::Stats(ref mut status) => {
+                if stats_ui(&button_sound, &profile, status) {
+                    game_state = GameState::Menu;
+                }
+            }
+            GameState::GameOver(ref mut screen) => {
+                match game_over_ui(&button_sound, screen, &mut high_scores) {
+                    GameOverAction::PlayAgain => {
+                        game = Some(new_game(&resources, &seed_input, &profile, screen.mode));
+                        game_state = GameState::Playing;
+                    }
+                    GameOverAction::Menu => game_state = GameState::Menu,
+                    GameOverAction::None => {}
+                }
+            }
+            GameState::HighScores(ref mut mode) => {
+                if high_scores_ui(&button_sound, &high_scores, mode) {
+                    game_state = GameState::Menu;
+                }
+            }
+            GameState::ModeSelect => match mode_select_ui(&button_sound) {
+                ModeSelectAction::Start(mode) => {
+                    game = Some(new_game(&resources, &seed_input, &profile, mode));
+                    game_state = GameState::Playing;
+                }
+                ModeSelectAction::DailyHistory => {
+                    game_state = GameState::DailyHistory(String::new())
+                }
+                ModeSelectAction::Back => game_state = GameState::Menu,
+                ModeSelectAction::None => {}
+            },
+            GameState::Replay(ref mut replay_game) => {
+                replay_game.update();
+                replay_game.draw();
+                if is_key_pressed(KeyCode::Escape) {
+                    replay_game.stop_music();
+                    play_opening_theme(&resources);
+                    game_state = GameState::Menu;
+                }
+            }
+            GameState::DailyHistory(ref mut status) => {
+                if daily_history_ui(&button_sound, &profile, status) {
+                    game_state = GameState::ModeSelect;
+                }
             }
         }
 
@@ -0,0 +1,219 @@
+use std::cmp::Ordering;
+
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    daily::{self, DailyModifier},
+    highscores::HighScoreEntry,
+    simulation::Simulation,
+    stats,
+};
+
+const TIME_ATTACK_SECONDS: f32 = 300.0;
+const DEPTH_RACE_ROW: i64 = 150;
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum GameMode {
+    #[default]
+    Endless,
+    TimeAttack,
+    DepthRace,
+    Zen,
+    Daily(i64), // Day of the challenge, in days since 1970-01-01
+}
+
+impl GameMode {
+    // The modes with a high score table, the daily challenge has its own history instead
+    pub const ALL: [GameMode; 4] = [
+        GameMode::Endless,
+        GameMode::TimeAttack,
+        GameMode::DepthRace,
+        GameMode::Zen,
+    ];
+
+    // Used as the key of the high score tables
+    pub fn id(self) -> &'static str {
+        match self {
+            GameMode::Endless => "endless",
+            GameMode::TimeAttack => "time_attack",
+            GameMode::DepthRace => "depth_race",
+            GameMode::Zen => "zen",
+            GameMode::Daily(_) => "daily",
+        }
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            GameMode::Endless => "Endless",
+            GameMode::TimeAttack => "Time Attack",
+            GameMode::DepthRace => "Depth Race",
+            GameMode::Zen => "Zen",
+            GameMode::Daily(_) => "Daily Dig",
+        }
+    }
+
+    pub fn description(self) -> String {
+        match self {
+            GameMode::Endless => String::from("Dig as deep as you can, watch out for the caves"),
+            GameMode::TimeAttack => format!(
+                "Get as much gold as you can in {}",
+                stats::format_duration(TIME_ATTACK_SECONDS)
+            ),
+            GameMode::DepthRace => format!("Reach row {} as fast as you can", DEPTH_RACE_ROW),
+            GameMode::Zen => String::from("Endless digging without enemies or timers"),
+            GameMode::Daily(day) => format!(
+                "Same world for everyone on {}, today: {}",
+                daily::format_day(day),
+                DailyModifier::for_day(day).name()
+            ),
+        }
+    }
+
+    pub fn rules(self) -> Box<dyn ModeRules> {
+        match self {
+            GameMode::Endless => Box::new(Endless),
+            GameMode::TimeAttack => Box::new(TimeAttack),
+            GameMode::DepthRace => Box::new(DepthRace),
+            GameMode::Zen => Box::new(Zen),
+            GameMode::Daily(day) => Box::new(Daily {
+                day,
+                modifier: DailyModifier::for_day(day),
+            }),
+        }
+    }
+
+    // How the high score table of this mode is sorted, better entries first
+    pub fn compare(self, a: &HighScoreEntry, b: &HighScoreEntry) -> Ordering {
+        match self {
+            GameMode::Endless | GameMode::Zen | GameMode::Daily(_) => b.score.cmp(&a.score),
+            GameMode::TimeAttack => b.gold.cmp(&a.gold),
+            GameMode::DepthRace => a.time.total_cmp(&b.time),
+        }
+    }
+
+    // The column the high score table shows for this mode
+    pub fn format_result(self, entry: &HighScoreEntry) -> String {
+        match self {
+            GameMode::Endless | GameMode::Zen | GameMode::Daily(_) => entry.score.to_string(),
+            GameMode::TimeAttack => format!("{} gold", entry.gold),
+            GameMode::DepthRace => stats::format_duration(entry.time),
+        }
+    }
+}
+
+// What makes each mode different, the game asks its rules every frame
+pub trait ModeRules {
+    // Called once the simulation is created or loaded
+    fn setup(&self, _simulation: &mut Simulation) {}
+
+    // The run ends when this returns true, on top of dying or retiring
+    fn is_finished(&self, _simulation: &Simulation) -> bool {
+        false
+    }
+
+    // Timed modes would get free gold out of the time the game was closed
+    fn has_offline_progress(&self) -> bool {
+        true
+    }
+
+    // Whether the run can go into the high score table, for example races that weren't finished can't
+    fn counts_for_high_scores(&self, _simulation: &Simulation) -> bool {
+        true
+    }
+
+    // Extra line for the HUD, like the time left
+    fn hud_text(&self, _simulation: &Simulation) -> Option<String> {
+        None
+    }
+}
+
+struct Endless;
+
+impl ModeRules for Endless {}
+
+struct TimeAttack;
+
+impl ModeRules for TimeAttack {
+    fn has_offline_progress(&self) -> bool {
+        false
+    }
+
+    fn is_finished(&self, simulation: &Simulation) -> bool {
+        simulation.run_time >= TIME_ATTACK_SECONDS
+    }
+
+    fn hud_text(&self, simulation: &Simulation) -> Option<String> {
+        Some(format!(
+            "Time Left = {}",
+            stats::format_duration(TIME_ATTACK_SECONDS - simulation.run_time)
+        ))
+    }
+}
+
+struct DepthRace;
+
+impl ModeRules for DepthRace {
+    fn has_offline_progress(&self) -> bool {
+        false
+    }
+
+    fn is_finished(&self, simulation: &Simulation) -> bool {
+        simulation.deepest_row >= DEPTH_RACE_ROW
+    }
+
+    fn counts_for_high_scores(&self, simulation: &Simulation) -> bool {
+        self.is_finished(simulation)
+    }
+
+    fn hud_text(&self, simulation: &Simulation) -> Option<String> {
+        Some(format!(
+            "Row {}/{} in {}",
+            simulation.deepest_row.min(DEPTH_RACE_ROW),
+            DEPTH_RACE_ROW,
+            stats::format_duration(simulation.run_time)
+        ))
+    }
+}
+
+struct Zen;
+
+impl ModeRules for Zen {
+    fn setup(&self, simulation: &mut Simulation) {
+        simulation.enemies_enabled = false;
+        simulation.enemies.clear();
+    }
+}
+
+struct Daily {
+    day: i64,
+    modifier: DailyModifier,
+}
+
+impl ModeRules for Daily {
+    fn setup(&self, simulation: &mut Simulation) {
+        match self.modifier {
+            DailyModifier::NoShop => simulation.shop_enabled = false,
+            DailyModifier::RockHeavy => simulation.set_strata_multiplier(daily::ROCK_HEAVY_STRATA),
+            DailyModifier::DoubleGold => simulation.bonuses.gold *= daily::DOUBLE_GOLD,
+        }
+    }
+
+    // Everyone should dig the same amount of time
+    fn has_offline_progress(&self) -> bool {
+        false
+    }
+
+    // Goes into the daily history instead
+    fn counts_for_high_scores(&self, _simulation: &Simulation) -> bool {
+        false
+    }
+
+    fn hud_text(&self, _simulation: &Simulation) -> Option<String> {
+        Some(format!(
+            "Daily Dig {}: {}",
+            daily::format_day(self.day),
+            self.modifier.name()
+        ))
+    }
+}
@@ -0,0 +1,51 @@
+use std::sync::OnceLock;
+
+use crate::rng::hash_point;
+
+// Smooth lattice (value) noise in the range [0, 1), always the same for a seed and a position
+pub fn value_noise(seed: u64, x: f32, y: f32) -> f32 {
+    let x0 = x.floor();
+    let y0 = y.floor();
+    let tx = smoothstep(x - x0);
+    let ty = smoothstep(y - y0);
+
+    let (xi, yi) = (x0 as i64, y0 as i64);
+    let top = lerp(lattice(seed, xi, yi), lattice(seed, xi + 1, yi), tx);
+    let bottom = lerp(lattice(seed, xi, yi + 1), lattice(seed, xi + 1, yi + 1), tx);
+    lerp(top, bottom, ty)
+}
+
+// Same as value_noise but remapped so the values are (almost) evenly spread over [0, 1).
+// That way `uniform_noise(..) > 1.0 - p` is true for roughly p of the cells, but the cells still come in clusters.
+pub fn uniform_noise(seed: u64, x: f32, y: f32) -> f32 {
+    let samples = noise_distribution();
+    let value = value_noise(seed, x, y);
+    samples.partition_point(|sample| *sample < value) as f32 / samples.len() as f32
+}
+
+// Sorted sample of the raw noise values, the distribution doesn't depend on the seed so it's only built once
+fn noise_distribution() -> &'static [f32] {
+    static SAMPLES: OnceLock<Vec<f32>> = OnceLock::new();
+    SAMPLES.get_or_init(|| {
+        let mut samples = Vec::with_capacity(64 * 64);
+        for i in 0..64 {
+            for j in 0..64 {
+                samples.push(value_noise(0, i as f32 * 0.37, j as f32 * 0.61));
+            }
+        }
+        samples.sort_by(f32::total_cmp);
+        samples
+    })
+}
+
+fn lattice(seed: u64, x: i64, y: i64) -> f32 {
+    (hash_point(seed, x, y) >> 40) as f32 / (1u64 << 24) as f32
+}
+
+fn smoothstep(t: f32) -> f32 {
+    t * t * (3.0 - 2.0 * t)
+}
+
+fn lerp(a: f32, b: f32, t: f32) -> f32 {
+    a + (b - a) * t
+}
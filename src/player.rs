@@ -3,31 +3,101 @@ use macroquad::{
     texture::Texture2D,
 };
 
-use crate::shape::Shape;
+use macroquad::math::{vec2, Vec2};
+
+use crate::{shape::Shape, upgrades::PickaxeId};
 
 const MOVEMENT_SPEED: f32 = 1.0;
+pub const WALK_SPEED: f32 = 150.0;
+pub const JUMP_SPEED: f32 = 260.0; // A bit more than one block high
+pub const CLIMB_SPEED: f32 = 120.0; // On ladders
+pub const WALL_CLIMB_SPEED: f32 = 60.0; // Up the walls of a shaft
+const HITBOX_INSET: f32 = 4.0; // The hitbox is a bit thinner than the sprite so you can fall down 1 block wide holes
+pub const MAX_HEALTH: i32 = 100;
+pub const PLAYER_SIZE: f32 = 32.0;
+const HURT_COOLDOWN: f32 = 1.0; // Seconds the player can't be hurt again after getting hit
 
-#[derive(PartialEq)]
-pub enum Pickaxe {
-    Normal,
-    Iron,
-    Gold,
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Facing {
+    Left,
+    Right,
+    Down,
 }
 
+// Simulation side of the player, it only knows about physics and the equipped pickaxe
 pub struct Player {
     pub shape: Shape,
     pub speed: f32,
     pub offset_y: f32, // Add this field to track vertical offset, to fix gravity jankiness
-    pub current_pickaxe: Pickaxe,
-
-    pub texture: Texture2D,
-    pub sprite: AnimatedSprite,
+    pub current_pickaxe: PickaxeId,
+    pub durability: u32, // Left on the current pickaxe, it's broken at 0
+    pub facing: Facing,
+    pub health: i32,
+    pub hurt_timer: f32,
+    pub grounded: bool, // Standing on a block, so it can jump
+    pub climbing: bool, // On a ladder or up a wall, gravity doesn't pull while climbing
+    // Top-left corner before the last tick, drawing blends between both
+    pub previous_position: Vec2,
 }
 
 impl Player {
-    pub fn new(shape: Shape, texture: Texture2D) -> Self {
+    pub fn new(shape: Shape, current_pickaxe: PickaxeId, durability: u32) -> Self {
         let speed = MOVEMENT_SPEED;
+        let previous_position = vec2(shape.x, shape.y);
+
+        Self {
+            shape,
+            speed,
+            offset_y: 0.0,
+            current_pickaxe,
+            durability,
+            facing: Facing::Down,
+            health: MAX_HEALTH,
+            hurt_timer: 0.0,
+            grounded: false,
+            climbing: false,
+            previous_position,
+        }
+    }
+
+    pub fn is_dead(&self) -> bool {
+        self.health <= 0
+    }
+
+    // Returns false if the player was still recovering from the last hit
+    pub fn take_damage(&mut self, damage: i32) -> bool {
+        if self.hurt_timer > 0.0 {
+            return false;
+        }
+
+        self.health = (self.health - damage).max(0);
+        self.hurt_timer = HURT_COOLDOWN;
+        true
+    }
+
+    // Shape used for collisions, the x axis is inset by HITBOX_INSET on both sides
+    pub fn hitbox(&self) -> Shape {
+        Shape {
+            x: self.shape.x + HITBOX_INSET,
+            y: self.shape.y,
+            size: vec2(self.shape.size.x - HITBOX_INSET * 2.0, self.shape.size.y),
+            color: self.shape.color,
+        }
+    }
+
+    pub fn set_hitbox_x(&mut self, x: f32) {
+        self.shape.x = x - HITBOX_INSET;
+    }
+}
+
+// Rendering side of the player, owned by the frontend
+pub struct PlayerSprite {
+    pub texture: Texture2D,
+    pub sprite: AnimatedSprite,
+}
 
+impl PlayerSprite {
+    pub fn new(texture: Texture2D) -> Self {
         let sprite = AnimatedSprite::new(
             32,
             32,
@@ -48,14 +118,7 @@ impl Player {
             true,
         );
 
-        Self {
-            shape,
-            speed,
-            offset_y: 0.0,
-            sprite,
-            texture,
-            current_pickaxe: Pickaxe::Normal,
-        }
+        Self { texture, sprite }
     }
 
     pub fn swap_texture(&mut self, texture: Texture2D) {
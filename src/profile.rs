@@ -0,0 +1,228 @@
+use std::collections::{BTreeMap, BTreeSet};
+
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    daily::DailyResult,
+    save::{self, SaveError},
+    stats::Statistics,
+    upgrades::{PrestigeEffect, PrestigeUpgradeId, UpgradeCatalog},
+};
+
+// Bump this whenever the profile format changes and add a step to `migrate`
+pub const PROFILE_VERSION: u32 = 4;
+const PROFILE_FILE: &str = "dwarfing_profile.json";
+
+// Progress that is kept between runs, unlike the save file which only holds the current run
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct Profile {
+    pub version: u32,
+    pub prestige_level: u32,
+    pub runes: u32,
+    // Permanent upgrade levels by id
+    pub upgrades: BTreeMap<String, u32>,
+    pub achievements: BTreeSet<String>, // Unlocked achievement ids
+    pub stats: Statistics,
+    pub daily_history: Vec<DailyResult>, // Oldest day first
+    // Set when the profile file couldn't be loaded, so this one never writes over it
+    #[serde(skip)]
+    pub read_only: bool,
+}
+
+// Multipliers the simulation gets from the permanent upgrades
+#[derive(Clone, Copy, Debug, Serialize, Deserialize)]
+pub struct Bonuses {
+    pub damage: f32,
+    pub gold: f32,
+    pub fall_speed: f32,
+}
+
+impl Default for Bonuses {
+    fn default() -> Self {
+        Self {
+            damage: 1.0,
+            gold: 1.0,
+            fall_speed: 1.0,
+        }
+    }
+}
+
+impl Default for Profile {
+    fn default() -> Self {
+        Self {
+            version: PROFILE_VERSION,
+            prestige_level: 0,
+            runes: 0,
+            upgrades: BTreeMap::new(),
+            achievements: BTreeSet::new(),
+            stats: Statistics::default(),
+            daily_history: Vec::new(),
+            read_only: false,
+        }
+    }
+}
+
+impl Profile {
+    pub fn upgrade_level(&self, id: &str) -> u32 {
+        self.upgrades.get(id).copied().unwrap_or(0)
+    }
+
+    pub fn bonuses(&self, catalog: &UpgradeCatalog) -> Bonuses {
+        let mut bonuses = Bonuses::default();
+        let Some(prestige) = &catalog.prestige else {
+            return bonuses;
+        };
+
+        for (_, upgrade) in prestige.upgrades() {
+            let bonus = upgrade.per_level * self.upgrade_level(&upgrade.id) as f32;
+            match upgrade.effect {
+                PrestigeEffect::Damage => bonuses.damage += bonus,
+                PrestigeEffect::Gold => bonuses.gold += bonus,
+                PrestigeEffect::FallSpeed => bonuses.fall_speed += bonus,
+            }
+        }
+        bonuses
+    }
+
+    // Why a permanent upgrade can't be bought right now, or None if it can
+    pub fn upgrade_blocker(
+        &self,
+        catalog: &UpgradeCatalog,
+        id: PrestigeUpgradeId,
+    ) -> Option<String> {
+        let upgrade = catalog.prestige.as_ref()?.upgrade(id);
+        let level = self.upgrade_level(&upgrade.id);
+        if level >= upgrade.max_level {
+            return Some(String::from("Maxed out"));
+        }
+        if self.runes < upgrade.price(level) {
+            return Some(String::from("Not enough runes"));
+        }
+        None
+    }
+
+    pub fn buy_upgrade(&mut self, catalog: &UpgradeCatalog, id: PrestigeUpgradeId) {
+        let Some(prestige) = &catalog.prestige else {
+            return;
+        };
+        if self.upgrade_blocker(catalog, id).is_some() {
+            return;
+        }
+
+        let upgrade = prestige.upgrade(id);
+        let level = self.upgrade_level(&upgrade.id);
+        self.runes -= upgrade.price(level);
+        self.upgrades.insert(upgrade.id.clone(), level + 1);
+    }
+
+    pub fn add_prestige(&mut self, runes: u32) {
+        self.prestige_level += 1;
+        self.runes += runes;
+    }
+
+    // Only the best run of each day is kept, the others just count as attempts
+    pub fn record_daily(&mut self, result: DailyResult) {
+        match self
+            .daily_history
+            .iter_mut()
+            .find(|other| other.day == result.day)
+        {
+            Some(best) => {
+                let attempts = best.attempts + 1;
+                if result.score > best.score {
+                    *best = result;
+                }
+                best.attempts = attempts;
+            }
+            None => {
+                self.daily_history.push(DailyResult {
+                    attempts: 1,
+                    ..result
+                });
+                self.daily_history.sort_by_key(|result| result.day);
+            }
+        }
+    }
+}
+
+// Returns a fresh profile when there is no profile file yet
+pub fn load() -> Result<Profile, SaveError> {
+    let contents = match std::fs::read_to_string(PROFILE_FILE) {
+        Ok(contents) => contents,
+        Err(err) if err.kind() == std::io::ErrorKind::NotFound => return Ok(Profile::default()),
+        Err(err) => return Err(err.into()),
+    };
+
+    let value: serde_json::Value = serde_json::from_str(&contents)?;
+    let version = value
+        .get("version")
+        .and_then(|version| version.as_u64())
+        .ok_or_else(|| SaveError::Invalid(String::from("missing version")))?
+        as u32;
+
+    let value = migrate(value, version)?;
+    Ok(serde_json::from_value(value)?)
+}
+
+// Does nothing for a read only profile, the menu already told the player it isn't kept
+pub fn write(profile: &Profile) -> Result<(), SaveError> {
+    if profile.read_only {
+        return Ok(());
+    }
+    save::write_atomic(PROFILE_FILE, &serde_json::to_string(profile)?)
+}
+
+// Upgrades older profiles to the current format one version at a time
+fn migrate(mut value: serde_json::Value, version: u32) -> Result<serde_json::Value, SaveError> {
+    match version {
+        // The profile gets written back later, so it has to remember it is up to date now
+        PROFILE_VERSION => {
+            value["version"] = serde_json::json!(PROFILE_VERSION);
+            Ok(value)
+        }
+        // v2 added the achievements
+        1 => {
+            value["achievements"] = serde_json::json!({});
+            migrate(value, 2)
+        }
+        // v3 moved the achievement counters into the lifetime statistics
+        2 => {
+            let mut progress = value["achievements"].take();
+            let mut stats = serde_json::to_value(Statistics::default())?;
+            if let Some(blocks) = progress["blocks_destroyed"].as_object() {
+                for (id, destroyed) in blocks {
+                    stats["blocks"][id] = serde_json::json!({
+                        "destroyed": destroyed,
+                        "clicks": 0,
+                        "damage": 0,
+                    });
+                }
+            }
+            for counter in [
+                "enemies_killed",
+                "gold_earned",
+                "deepest_row",
+                "seconds_played",
+                "pickaxes_bought",
+            ] {
+                if !progress[counter].is_null() {
+                    stats[counter] = progress[counter].clone();
+                }
+            }
+
+            value["achievements"] = match progress["unlocked"].take() {
+                serde_json::Value::Null => serde_json::json!([]),
+                unlocked => unlocked,
+            };
+            value["stats"] = stats;
+            migrate(value, 3)
+        }
+        // v4 added the daily challenge history
+        3 => {
+            value["daily_history"] = serde_json::json!([]);
+            migrate(value, 4)
+        }
+        version if version > PROFILE_VERSION => Err(SaveError::NewerVersion(version)),
+        version => Err(SaveError::UnsupportedVersion(version)),
+    }
+}
@@ -0,0 +1,412 @@
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    block_registry::BlockRegistry,
+    items::{ItemId, ItemRegistry},
+    upgrades::{PickaxeId, StorageUpgradeId, UpgradeCatalog},
+};
+
+// Index of a recipe inside the book
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub struct RecipeId(pub usize);
+
+#[derive(Deserialize)]
+struct IngredientFile {
+    item: String,
+    count: u32,
+}
+
+#[derive(Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+enum OutputFile {
+    Item { item: String, count: u32 },
+    Pickaxe { pickaxe: String },
+    StorageUpgrade { storage_upgrade: String },
+}
+
+#[derive(Deserialize)]
+struct RecipeFile {
+    id: String,
+    name: String,
+    time: f32,
+    ingredients: Vec<IngredientFile>,
+    output: OutputFile,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum RecipeOutput {
+    Item { item: ItemId, count: u32 },
+    // Upgrades from the shop, crafting them is an alternative to paying gold
+    Pickaxe(PickaxeId),
+    StorageUpgrade(StorageUpgradeId),
+}
+
+#[derive(Debug)]
+pub struct Recipe {
+    pub id: String,
+    pub name: String,
+    pub time: f32, // Seconds in the crafting queue
+    pub ingredients: Vec<(ItemId, u32)>,
+    pub output: RecipeOutput,
+}
+
+#[derive(Clone, Copy, PartialEq)]
+enum Visit {
+    New,
+    InPath,
+    Done,
+}
+
+// Everything that can be crafted, loaded from recipes.json
+#[derive(Debug)]
+pub struct RecipeBook {
+    recipes: Vec<Recipe>,
+}
+
+impl RecipeBook {
+    // Ids are resolved against the other definition files, then the whole graph is checked: no item can
+    // be needed to make itself and every item has to come from a block or a recipe that can be made
+    pub fn from_json(
+        json: &str,
+        blocks: &BlockRegistry,
+        items: &ItemRegistry,
+        upgrades: &UpgradeCatalog,
+    ) -> Result<Self, String> {
+        let files: Vec<RecipeFile> = serde_json::from_str(json).map_err(|err| err.to_string())?;
+
+        let find_item = |recipe: &str, id: &str| {
+            items
+                .find(id)
+                .ok_or_else(|| format!("recipe '{}' uses the unknown item '{}'", recipe, id))
+        };
+
+        let mut recipes: Vec<Recipe> = Vec::new();
+        for file in files {
+            if recipes.iter().any(|other| other.id == file.id) {
+                return Err(format!("duplicated recipe id '{}'", file.id));
+            }
+            if file.time <= 0.0 {
+                return Err(format!("recipe '{}' needs a positive time", file.id));
+            }
+            if file.ingredients.is_empty() {
+                return Err(format!(
+                    "recipe '{}' needs at least one ingredient",
+                    file.id
+                ));
+            }
+
+            let mut ingredients = Vec::new();
+            for ingredient in &file.ingredients {
+                if ingredient.count == 0 {
+                    return Err(format!(
+                        "recipe '{}' needs a positive count of '{}'",
+                        file.id, ingredient.item
+                    ));
+                }
+                ingredients.push((find_item(&file.id, &ingredient.item)?, ingredient.count));
+            }
+
+            let output = match &file.output {
+                OutputFile::Item { count: 0, .. } => {
+                    return Err(format!(
+                        "recipe '{}' needs a positive output count",
+                        file.id
+                    ));
+                }
+                OutputFile::Item { item, count } => RecipeOutput::Item {
+                    item: find_item(&file.id, item)?,
+                    count: *count,
+                },
+                OutputFile::Pickaxe { pickaxe } => {
+                    RecipeOutput::Pickaxe(upgrades.find_pickaxe(pickaxe).ok_or_else(|| {
+                        format!(
+                            "recipe '{}' makes the unknown pickaxe '{}'",
+                            file.id, pickaxe
+                        )
+                    })?)
+                }
+                OutputFile::StorageUpgrade { storage_upgrade } => RecipeOutput::StorageUpgrade(
+                    upgrades
+                        .find_storage_upgrade(storage_upgrade)
+                        .ok_or_else(|| {
+                            format!(
+                                "recipe '{}' makes the unknown storage upgrade '{}'",
+                                file.id, storage_upgrade
+                            )
+                        })?,
+                ),
+            };
+
+            recipes.push(Recipe {
+                id: file.id,
+                name: file.name,
+                time: file.time,
+                ingredients,
+                output,
+            });
+        }
+
+        if let Some(item) = Self::find_cycle(&recipes, items) {
+            return Err(format!(
+                "the recipes go in a circle through the item '{}'",
+                items.get(item).id
+            ));
+        }
+        if let Some(item) = Self::find_unreachable(&recipes, blocks, items) {
+            return Err(format!(
+                "the item '{}' can't be mined or crafted",
+                items.get(item).id
+            ));
+        }
+
+        Ok(Self { recipes })
+    }
+
+    pub fn get(&self, id: RecipeId) -> &Recipe {
+        &self.recipes[id.0]
+    }
+
+    pub fn find(&self, id: &str) -> Option<RecipeId> {
+        self.recipes
+            .iter()
+            .position(|recipe| recipe.id == id)
+            .map(RecipeId)
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = (RecipeId, &Recipe)> {
+        self.recipes
+            .iter()
+            .enumerate()
+            .map(|(index, recipe)| (RecipeId(index), recipe))
+    }
+
+    //
+    // HELPERS
+    //
+
+    // Every ingredient points to the item its recipe makes, a depth first search finds the first item
+    // that can be reached from itself
+    fn find_cycle(recipes: &[Recipe], items: &ItemRegistry) -> Option<ItemId> {
+        let item_count = items.iter().count();
+        let mut edges = vec![Vec::new(); item_count];
+        for recipe in recipes {
+            if let RecipeOutput::Item { item, .. } = recipe.output {
+                for (ingredient, _) in &recipe.ingredients {
+                    edges[ingredient.0].push(item);
+                }
+            }
+        }
+
+        let mut visits = vec![Visit::New; item_count];
+        (0..item_count).find_map(|item| Self::visit(ItemId(item), &edges, &mut visits))
+    }
+
+    fn visit(item: ItemId, edges: &[Vec<ItemId>], visits: &mut [Visit]) -> Option<ItemId> {
+        match visits[item.0] {
+            Visit::InPath => return Some(item),
+            Visit::Done => return None,
+            Visit::New => {}
+        }
+
+        visits[item.0] = Visit::InPath;
+        let cycle = edges[item.0]
+            .iter()
+            .find_map(|&next| Self::visit(next, edges, visits));
+        visits[item.0] = Visit::Done;
+        cycle
+    }
+
+    // Starts from what the blocks drop and keeps adding the outputs of the recipes that can be made with
+    // what's there, whatever is left out can never end up in the backpack
+    fn find_unreachable(
+        recipes: &[Recipe],
+        blocks: &BlockRegistry,
+        items: &ItemRegistry,
+    ) -> Option<ItemId> {
+        let mut reachable = vec![false; items.iter().count()];
+        for (block_id, _) in blocks.iter() {
+            if let Some(item) = items.drop_of(block_id) {
+                reachable[item.0] = true;
+            }
+        }
+
+        let mut changed = true;
+        while changed {
+            changed = false;
+            for recipe in recipes {
+                let RecipeOutput::Item { item, .. } = recipe.output else {
+                    continue;
+                };
+                let can_make = recipe
+                    .ingredients
+                    .iter()
+                    .all(|(ingredient, _)| reachable[ingredient.0]);
+                if can_make && !reachable[item.0] {
+                    reachable[item.0] = true;
+                    changed = true;
+                }
+            }
+        }
+
+        reachable
+            .iter()
+            .position(|&reachable| !reachable)
+            .map(ItemId)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const BLOCKS: &str = r#"[
+        { "id": "dirt", "texture": "", "base_hp": 1, "score": 1, "gold": 1, "hardness": 0, "spawn": null },
+        { "id": "ore", "texture": "", "base_hp": 1, "score": 1, "gold": 0, "hardness": 0, "spawn": null, "drop": "ore" }
+    ]"#;
+
+    const UPGRADES: &str = r#"{
+        "pickaxes": [
+            { "id": "normal", "name": "Basic", "price": 0, "damage": 1, "texture": "", "tier": 0, "requires": null },
+            { "id": "iron", "name": "Iron", "price": 50, "damage": 2, "texture": "", "tier": 1, "requires": "normal" }
+        ]
+    }"#;
+
+    fn item(id: &str) -> String {
+        format!(
+            r#"{{ "id": "{}", "name": "{}", "weight": 1.0, "price": 1, "stack": 10 }}"#,
+            id, id
+        )
+    }
+
+    fn recipe(id: &str, ingredients: &[&str], output: &str) -> String {
+        let ingredients: Vec<String> = ingredients
+            .iter()
+            .map(|ingredient| format!(r#"{{ "item": "{}", "count": 1 }}"#, ingredient))
+            .collect();
+        format!(
+            r#"{{ "id": "{}", "name": "{}", "time": 1.0, "ingredients": [{}], "output": {{ "type": "item", "item": "{}", "count": 1 }} }}"#,
+            id,
+            id,
+            ingredients.join(","),
+            output
+        )
+    }
+
+    // Builds a book out of the item ids and recipes, on top of the test blocks where only "ore" drops something
+    fn book(items: &[&str], recipes: &[String]) -> Result<RecipeBook, String> {
+        let blocks = BlockRegistry::from_json(BLOCKS).unwrap();
+        let items: Vec<String> = items.iter().map(|id| item(id)).collect();
+        let items = ItemRegistry::from_json(&format!("[{}]", items.join(",")), &blocks).unwrap();
+        let upgrades = UpgradeCatalog::from_json(UPGRADES).unwrap();
+        RecipeBook::from_json(
+            &format!("[{}]", recipes.join(",")),
+            &blocks,
+            &items,
+            &upgrades,
+        )
+    }
+
+    #[test]
+    fn shipped_recipes_are_valid() {
+        let blocks = BlockRegistry::from_json(include_str!("../assets/blocks.json")).unwrap();
+        let items = ItemRegistry::from_json(include_str!("../assets/items.json"), &blocks).unwrap();
+        let upgrades = UpgradeCatalog::from_json(include_str!("../assets/upgrades.json")).unwrap();
+        let book = RecipeBook::from_json(
+            include_str!("../assets/recipes.json"),
+            &blocks,
+            &items,
+            &upgrades,
+        );
+        assert!(book.is_ok(), "{:?}", book.err());
+    }
+
+    #[test]
+    fn chains_of_recipes_are_allowed() {
+        let book = book(
+            &["ore", "bar", "part"],
+            &[
+                recipe("smelt", &["ore"], "bar"),
+                recipe("forge", &["bar", "ore"], "part"),
+            ],
+        )
+        .unwrap();
+
+        let forge = book.get(book.find("forge").unwrap());
+        assert_eq!(forge.ingredients, vec![(ItemId(1), 1), (ItemId(0), 1)]);
+        assert_eq!(
+            forge.output,
+            RecipeOutput::Item {
+                item: ItemId(2),
+                count: 1
+            }
+        );
+    }
+
+    #[test]
+    fn upgrades_can_be_crafted() {
+        let upgrade = r#"{ "id": "forge_iron", "name": "Iron", "time": 1.0, "ingredients": [{ "item": "ore", "count": 3 }], "output": { "type": "pickaxe", "pickaxe": "iron" } }"#;
+        let book = book(&["ore"], &[String::from(upgrade)]).unwrap();
+        let forge_iron = book.get(RecipeId(0));
+        assert_eq!(forge_iron.output, RecipeOutput::Pickaxe(PickaxeId(1)));
+        assert_eq!(forge_iron.ingredients, vec![(ItemId(0), 3)]);
+    }
+
+    #[test]
+    fn recipes_that_make_their_own_ingredient_are_rejected() {
+        let result = book(&["ore", "bar"], &[recipe("loop", &["ore", "bar"], "bar")]);
+        assert!(result
+            .unwrap_err()
+            .contains("circle through the item 'bar'"));
+    }
+
+    #[test]
+    fn cycles_across_recipes_are_rejected() {
+        let result = book(
+            &["ore", "a", "b", "c"],
+            &[
+                recipe("a", &["ore"], "a"),
+                recipe("b", &["a"], "b"),
+                recipe("c", &["b"], "c"),
+                recipe("back_to_a", &["c"], "a"),
+            ],
+        );
+        assert!(result.unwrap_err().contains("go in a circle"));
+    }
+
+    #[test]
+    fn items_nothing_drops_or_makes_are_rejected() {
+        let result = book(&["ore", "bar", "gem"], &[recipe("smelt", &["ore"], "bar")]);
+        assert_eq!(
+            result.unwrap_err(),
+            "the item 'gem' can't be mined or crafted"
+        );
+    }
+
+    #[test]
+    fn items_made_only_from_unreachable_items_are_rejected() {
+        let result = book(
+            &["ore", "gem", "ring"],
+            &[recipe("ring", &["ore", "gem"], "ring")],
+        );
+        assert_eq!(
+            result.unwrap_err(),
+            "the item 'gem' can't be mined or crafted"
+        );
+    }
+
+    #[test]
+    fn unknown_ids_are_rejected() {
+        let result = book(&["ore", "bar"], &[recipe("smelt", &["mithril"], "bar")]);
+        assert_eq!(
+            result.unwrap_err(),
+            "recipe 'smelt' uses the unknown item 'mithril'"
+        );
+
+        let upgrade = r#"{ "id": "forge", "name": "Forge", "time": 1.0, "ingredients": [{ "item": "ore", "count": 1 }], "output": { "type": "pickaxe", "pickaxe": "diamond" } }"#;
+        let result = book(&["ore"], &[String::from(upgrade)]);
+        assert_eq!(
+            result.unwrap_err(),
+            "recipe 'forge' makes the unknown pickaxe 'diamond'"
+        );
+    }
+}
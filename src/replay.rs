@@ -0,0 +1,178 @@
+use std::rc::Rc;
+
+use macroquad::prelude::*;
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    definitions::Definitions,
+    modes::GameMode,
+    profile::Bonuses,
+    save::{self, SaveError},
+    simulation::{Command, SimInput, Simulation},
+};
+
+// Bump this whenever the replay format or the outcome of the same inputs changes, old replays are just rejected
+pub const REPLAY_VERSION: u32 = 8;
+pub const REPLAY_FILE: &str = "dwarfing_replay.json";
+
+// Everything the simulation got in a single frame
+#[derive(Debug, Serialize, Deserialize)]
+pub struct ReplayFrame {
+    pub delta_time: f32,
+    pub move_x: f32,
+    pub move_y: f32,
+    pub jump_pressed: bool,
+    pub aim: Option<(f32, f32)>,
+    pub mine_pressed: bool,
+    pub commands: Vec<Command>,
+    pub view_top: Option<f32>,
+}
+
+// A run from its very first frame, feeding the frames back into a new simulation gives the exact same run
+#[derive(Debug, Serialize, Deserialize)]
+pub struct Replay {
+    pub version: u32,
+    pub seed: u64,
+    pub mode: GameMode,
+    pub bonuses: Bonuses, // From the profile, before the mode changes them
+    pub viewport: (f32, f32),
+    pub frames: Vec<ReplayFrame>,
+    // Simulation::state_hash after the last frame, set when the replay is written
+    pub final_hash: Option<u64>,
+}
+
+impl ReplayFrame {
+    pub fn new(input: &SimInput, delta_time: f32) -> Self {
+        Self {
+            delta_time,
+            move_x: input.move_x,
+            move_y: input.move_y,
+            jump_pressed: input.jump_pressed,
+            aim: input.aim.map(|aim| (aim.x, aim.y)),
+            mine_pressed: input.mine_pressed,
+            commands: input.commands.clone(),
+            view_top: input.view_top,
+        }
+    }
+
+    pub fn input(&self) -> SimInput {
+        SimInput {
+            move_x: self.move_x,
+            move_y: self.move_y,
+            jump_pressed: self.jump_pressed,
+            aim: self.aim.map(|(x, y)| vec2(x, y)),
+            mine_pressed: self.mine_pressed,
+            commands: self.commands.clone(),
+            view_top: self.view_top,
+        }
+    }
+}
+
+impl Replay {
+    pub fn new(seed: u64, mode: GameMode, bonuses: Bonuses, viewport: Vec2) -> Self {
+        Self {
+            version: REPLAY_VERSION,
+            seed,
+            mode,
+            bonuses,
+            viewport: (viewport.x, viewport.y),
+            frames: Vec::new(),
+            final_hash: None,
+        }
+    }
+
+    pub fn viewport(&self) -> Vec2 {
+        vec2(self.viewport.0, self.viewport.1)
+    }
+
+    // The simulation as it was before the first frame, the mode rules still have to be set up
+    pub fn simulation(&self, definitions: Rc<Definitions>) -> Simulation {
+        let mut simulation = Simulation::new(self.viewport(), self.seed, definitions);
+        simulation.bonuses = self.bonuses;
+        simulation
+    }
+
+    // Runs every frame without a window and checks the end result against the recorded one
+    pub fn verify(&self, definitions: Rc<Definitions>) -> Result<u64, String> {
+        let expected = self
+            .final_hash
+            .ok_or_else(|| String::from("the replay has no final hash"))?;
+
+        let mut simulation = self.simulation(definitions);
+        self.mode.rules().setup(&mut simulation);
+        for frame in &self.frames {
+            simulation.update(&frame.input(), frame.delta_time, self.viewport());
+        }
+
+        let hash = simulation.state_hash();
+        if hash != expected {
+            return Err(format!(
+                "the final state differs (expected {:016x}, got {:016x})",
+                expected, hash
+            ));
+        }
+        Ok(hash)
+    }
+}
+
+pub fn load(file: &str) -> Result<Replay, SaveError> {
+    let contents = std::fs::read_to_string(file)?;
+    let replay: Replay = serde_json::from_str(&contents)?;
+    match replay.version {
+        REPLAY_VERSION => Ok(replay),
+        version if version > REPLAY_VERSION => Err(SaveError::NewerVersion(version)),
+        version => Err(SaveError::UnsupportedVersion(version)),
+    }
+}
+
+pub fn write(replay: &Replay) -> Result<(), SaveError> {
+    save::write_atomic(REPLAY_FILE, &serde_json::to_string(replay)?)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const VIEWPORT: Vec2 = vec2(1056.0, 800.0);
+    const TICK: f32 = 1.0 / 60.0;
+
+    // Walks right and mines for a few seconds, recording every frame like the game does
+    fn record() -> Replay {
+        let definitions = Rc::new(Definitions::shipped());
+        let mut replay = Replay::new(42, GameMode::Endless, Bonuses::default(), VIEWPORT);
+        let mut simulation = replay.simulation(definitions);
+        replay.mode.rules().setup(&mut simulation);
+        for tick in 0..300 {
+            let input = SimInput {
+                move_x: if tick < 60 { 1.0 } else { 0.0 },
+                mine_pressed: tick >= 60,
+                ..Default::default()
+            };
+            replay.frames.push(ReplayFrame::new(&input, TICK));
+            simulation.update(&input, TICK, VIEWPORT);
+        }
+        replay.final_hash = Some(simulation.state_hash());
+        replay
+    }
+
+    #[test]
+    fn recorded_runs_verify() {
+        let replay = record();
+        assert_eq!(
+            replay.verify(Rc::new(Definitions::shipped())),
+            Ok(replay.final_hash.unwrap())
+        );
+    }
+
+    #[test]
+    fn changed_frames_fail_to_verify() {
+        let mut replay = record();
+        for frame in &mut replay.frames[..60] {
+            frame.move_x = -1.0;
+        }
+        assert!(replay
+            .verify(Rc::new(Definitions::shipped()))
+            .unwrap_err()
+            .contains("the final state differs"));
+    }
+}
@@ -1,3 +1,5 @@
+use std::{collections::HashMap, rc::Rc};
+
 use macroquad::{
     audio::{load_sound, Sound},
     color::WHITE,
@@ -6,17 +8,20 @@ use macroquad::{
     ui::{root_ui, Skin},
 };
 
+use crate::{
+    biome::BiomeId, block_registry::BlockId, definitions::Definitions, upgrades::PickaxeId,
+};
+
 #[derive(Clone, Debug)]
 pub struct Resources {
     // Textures
-    pub player_texture_basic: Texture2D,
-    pub player_texture_iron: Texture2D,
-    pub player_texture_gold: Texture2D,
-    pub dirt_block_texture: Texture2D,
-    pub rock_block_texture: Texture2D,
-    pub gold_block_texture: Texture2D,
+    pub player_textures: Vec<Texture2D>, // Indexed by PickaxeId
+    pub block_textures: Vec<Texture2D>,  // Indexed by BlockId
     pub game_background_texture: Texture2D,
 
+    // Definitions
+    pub definitions: Rc<Definitions>,
+
     // Images
     pub menu_background: Image,
     pub button_background: Image,
@@ -25,41 +30,31 @@ pub struct Resources {
     // Sounds
     pub opening_theme: Sound,
     pub start_button_sound: Sound,
-    pub game_theme: Sound,
+    pub biome_music: Vec<Sound>, // Indexed by BiomeId
     pub pickaxe_sound: Sound,
 }
 
 impl Resources {
     pub async fn new() -> Self {
-        let player_texture_basic: Texture2D = load_texture("sprites/player_basic.png")
-            .await
-            .expect("Can't load player texture");
-        player_texture_basic.set_filter(FilterMode::Nearest);
-
-        let player_texture_iron: Texture2D = load_texture("sprites/player_iron.png")
-            .await
-            .expect("Can't load player texture");
-        player_texture_iron.set_filter(FilterMode::Nearest);
-
-        let player_texture_gold: Texture2D = load_texture("sprites/player_gold.png")
-            .await
-            .expect("Can't load player texture");
-        player_texture_gold.set_filter(FilterMode::Nearest);
-
-        let dirt_block_texture: Texture2D = load_texture("blocks/dirt.png")
-            .await
-            .expect("Can't load dirt block texture");
-        dirt_block_texture.set_filter(FilterMode::Nearest);
-
-        let rock_block_texture: Texture2D = load_texture("blocks/stone.png")
-            .await
-            .expect("Can't load rock block texture");
-        dirt_block_texture.set_filter(FilterMode::Nearest);
+        let definitions = Definitions::load().await;
+
+        let mut player_textures = Vec::new();
+        for (_, pickaxe) in definitions.upgrades.pickaxes() {
+            let texture: Texture2D = load_texture(&pickaxe.texture)
+                .await
+                .expect("Can't load player texture");
+            texture.set_filter(FilterMode::Nearest);
+            player_textures.push(texture);
+        }
 
-        let gold_block_texture: Texture2D = load_texture("blocks/gold.png")
-            .await
-            .expect("Can't load gold block texture");
-        dirt_block_texture.set_filter(FilterMode::Nearest);
+        let mut block_textures = Vec::new();
+        for (_, block) in definitions.blocks.iter() {
+            let texture: Texture2D = load_texture(&block.texture)
+                .await
+                .unwrap_or_else(|_| panic!("Can't load {} block texture", block.id));
+            texture.set_filter(FilterMode::Nearest);
+            block_textures.push(texture);
+        }
 
         let game_background_texture: Texture2D = load_texture("resources/game_bg.png")
             .await
@@ -79,27 +74,48 @@ impl Resources {
         // Sounds
         let opening_theme = load_sound("sounds/opening-theme.ogg").await.unwrap();
         let start_button_sound = load_sound("sounds/button-game-start.ogg").await.unwrap();
-        let game_theme = load_sound("sounds/game-theme.ogg").await.unwrap();
         let pickaxe_sound = load_sound("sounds/pickaxe.ogg").await.unwrap();
 
+        // Biomes can share a track, so every file is only loaded once
+        let mut loaded_music: HashMap<String, Sound> = HashMap::new();
+        let mut biome_music = Vec::new();
+        for (_, biome) in definitions.biomes.iter() {
+            if !loaded_music.contains_key(&biome.music) {
+                let sound = load_sound(&biome.music)
+                    .await
+                    .unwrap_or_else(|_| panic!("Can't load {} music", biome.id));
+                loaded_music.insert(biome.music.clone(), sound);
+            }
+            biome_music.push(loaded_music[&biome.music].clone());
+        }
+
         Self {
-            player_texture_basic,
-            player_texture_iron,
-            player_texture_gold,
-            dirt_block_texture,
-            rock_block_texture,
-            gold_block_texture,
+            player_textures,
+            block_textures,
             game_background_texture,
+            definitions: Rc::new(definitions),
             menu_background,
             button_background,
             button_clicked_background,
             opening_theme,
             start_button_sound,
-            game_theme,
+            biome_music,
             pickaxe_sound,
         }
     }
 
+    pub fn block_texture(&self, id: BlockId) -> &Texture2D {
+        &self.block_textures[id.0]
+    }
+
+    pub fn player_texture(&self, id: PickaxeId) -> &Texture2D {
+        &self.player_textures[id.0]
+    }
+
+    pub fn biome_music(&self, id: BiomeId) -> &Sound {
+        &self.biome_music[id.0]
+    }
+
     pub fn build_ui(self) {
         let window_style = root_ui()
             .style_builder()
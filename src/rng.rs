@@ -0,0 +1,88 @@
+use std::hash::Hasher;
+
+// Small deterministic random number generator (SplitMix64), we can't use the global macroquad rng
+// because every run (and every row) has to be reproducible from the world seed.
+pub struct Rng {
+    state: u64,
+}
+
+impl Rng {
+    pub fn new(seed: u64) -> Self {
+        Self { state: seed }
+    }
+
+    // Rng for a single row of blocks, only depends on the world seed and the row depth
+    // so the terrain is always the same no matter in which order (or how fast) rows are generated
+    pub fn for_row(world_seed: u64, row: i64) -> Self {
+        Self::new(mix(world_seed ^ mix(row as u64)))
+    }
+
+    pub fn next_u64(&mut self) -> u64 {
+        self.state = self.state.wrapping_add(0x9E37_79B9_7F4A_7C15);
+        mix(self.state)
+    }
+
+    // Random number in the range [0, 1)
+    pub fn next_f32(&mut self) -> f32 {
+        (self.next_u64() >> 40) as f32 / (1u64 << 24) as f32
+    }
+}
+
+// Hash of a lattice point, used by the terrain noise
+pub fn hash_point(seed: u64, x: i64, y: i64) -> u64 {
+    mix(seed ^ mix((x as u64) ^ mix(y as u64)))
+}
+
+fn mix(value: u64) -> u64 {
+    let mut z = value;
+    z = (z ^ (z >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+    z = (z ^ (z >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+    z ^ (z >> 31)
+}
+
+// FNV-1a, unlike the std hasher it's guaranteed to give the same result on every machine and Rust version
+pub struct Fnv1a {
+    hash: u64,
+}
+
+impl Fnv1a {
+    pub fn new() -> Self {
+        Self {
+            hash: 0xCBF2_9CE4_8422_2325,
+        }
+    }
+}
+
+impl Hasher for Fnv1a {
+    fn write(&mut self, bytes: &[u8]) {
+        for byte in bytes {
+            self.hash ^= *byte as u64;
+            self.hash = self.hash.wrapping_mul(0x0100_0000_01B3);
+        }
+    }
+
+    fn finish(&self) -> u64 {
+        self.hash
+    }
+}
+
+// Turns whatever the player typed into a seed, numbers are used as-is and any other text is hashed
+pub fn seed_from_text(text: &str) -> Option<u64> {
+    let text = text.trim();
+    if text.is_empty() {
+        return None;
+    }
+
+    if let Ok(seed) = text.parse::<u64>() {
+        return Some(seed);
+    }
+
+    let mut hasher = Fnv1a::new();
+    hasher.write(text.as_bytes());
+    Some(hasher.finish())
+}
+
+// Seed used when the player didn't type one
+pub fn random_seed() -> u64 {
+    mix(macroquad::miniquad::date::now().to_bits())
+}
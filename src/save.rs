@@ -0,0 +1,320 @@
+use std::fmt;
+
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    modes::GameMode,
+    player::{MAX_HEALTH, PLAYER_SIZE},
+    WINDOW_SIZE_X,
+};
+
+// Bump this whenever the save format changes and add a step to `migrate`
+pub const SAVE_VERSION: u32 = 13;
+const SAVE_FILE: &str = "dwarfing_save.json";
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct SaveGame {
+    pub version: u32,
+    pub seed: u64,
+    pub score: SavedScore,
+    pub pickaxe: String,
+    pub durability: u32,
+    pub player_x: f32, // Left edge of the player's sprite
+    pub offset_y: f32,
+    pub last_row: i64,
+    pub blocks: Vec<SavedBlock>,
+    pub chunks: Vec<SavedChunk>, // The world above the screen
+    pub ladders: u32,            // Not placed yet
+    pub ladder_tiles: Vec<(i32, i64)>,
+    pub health: i32,
+    pub enemies: Vec<SavedEnemy>,
+    pub abilities: Vec<SavedAbility>,
+    pub gold_rush: Option<SavedGoldRush>,
+    pub auto_miners: Vec<SavedAutoMiner>,
+    pub inventory: Vec<SavedItem>,
+    pub storage_upgrades: Vec<String>, // Only the owned ones
+    pub crafting: Vec<SavedCraftJob>,  // In queue order
+    // Unix time in seconds, used to work out the offline earnings. 0 means unknown
+    pub saved_at: u64,
+    pub deepest_row: i64,
+    pub run_time: f32,   // Seconds
+    pub started_at: u64, // Unix time in seconds, 0 means unknown
+    pub mode: GameMode,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct SavedScore {
+    pub current_score: i32,
+    pub blocks_destroyed: i32,
+    pub gold: i32,
+}
+
+// Blocks are stored by tile coordinates, row 0 is the surface line
+#[derive(Debug, Serialize, Deserialize)]
+pub struct SavedBlock {
+    pub column: i32,
+    pub row: i64,
+    pub block: String,
+    pub hp: i32,
+}
+
+// A chunk that was off screen, packed almost like the game keeps it in memory
+#[derive(Debug, Serialize, Deserialize)]
+pub struct SavedChunk {
+    pub chunk: i64,               // The first row of the chunk is chunk * CHUNK_ROWS
+    pub width: i32,               // Columns, loading fails if it isn't the width of the world
+    pub blocks: Vec<String>,      // Every block used in the chunk
+    pub tiles: Vec<u16>,          // Index in `blocks` + 1 of every tile row by row, 0 is empty
+    pub damaged: Vec<(u32, i32)>, // Tile index and hp of the blocks that aren't at full hp
+}
+
+// Enemies move freely, so unlike blocks they are stored by world position
+#[derive(Debug, Serialize, Deserialize)]
+pub struct SavedEnemy {
+    pub enemy: String,
+    pub x: f32,
+    pub y: f32,
+    pub hp: i32,
+}
+
+// Only owned abilities are stored
+#[derive(Debug, Serialize, Deserialize)]
+pub struct SavedAbility {
+    pub ability: String,
+    pub cooldown: f32,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct SavedGoldRush {
+    pub remaining: f32,
+    pub multiplier: i32,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct SavedAutoMiner {
+    pub auto_miner: String,
+    pub count: u32,
+}
+
+// Only the items the player is carrying are stored
+#[derive(Debug, Serialize, Deserialize)]
+pub struct SavedItem {
+    pub item: String,
+    pub count: u32,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct SavedCraftJob {
+    pub recipe: String,
+    pub elapsed: f32,
+}
+
+#[derive(Debug)]
+pub enum SaveError {
+    Io(std::io::Error),
+    Corrupt(serde_json::Error),
+    NewerVersion(u32),
+    UnsupportedVersion(u32),
+    Invalid(String),
+}
+
+impl fmt::Display for SaveError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SaveError::Io(err) => write!(f, "could not access the save file ({})", err),
+            SaveError::Corrupt(err) => write!(f, "the save file is corrupt ({})", err),
+            SaveError::NewerVersion(version) => write!(
+                f,
+                "the save file was made by a newer version of the game (v{})",
+                version
+            ),
+            SaveError::UnsupportedVersion(version) => {
+                write!(f, "the save file version (v{}) is not supported", version)
+            }
+            SaveError::Invalid(reason) => write!(f, "the save file is invalid ({})", reason),
+        }
+    }
+}
+
+impl From<std::io::Error> for SaveError {
+    fn from(err: std::io::Error) -> Self {
+        SaveError::Io(err)
+    }
+}
+
+impl From<serde_json::Error> for SaveError {
+    fn from(err: serde_json::Error) -> Self {
+        SaveError::Corrupt(err)
+    }
+}
+
+// Returns Ok(None) when there is no save file yet
+pub fn load() -> Result<Option<SaveGame>, SaveError> {
+    let contents = match std::fs::read_to_string(SAVE_FILE) {
+        Ok(contents) => contents,
+        Err(err) if err.kind() == std::io::ErrorKind::NotFound => return Ok(None),
+        Err(err) => return Err(err.into()),
+    };
+    parse(&contents).map(Some)
+}
+
+// A save of any supported version, upgraded to the current one
+fn parse(contents: &str) -> Result<SaveGame, SaveError> {
+    let value: serde_json::Value = serde_json::from_str(contents)?;
+    let version = value
+        .get("version")
+        .and_then(|version| version.as_u64())
+        .ok_or_else(|| SaveError::Invalid(String::from("missing version")))?
+        as u32;
+
+    let value = migrate(value, version)?;
+    Ok(serde_json::from_value(value)?)
+}
+
+pub fn write(save: &SaveGame) -> Result<(), SaveError> {
+    write_atomic(SAVE_FILE, &serde_json::to_string(save)?)
+}
+
+// Writes to a temporary file first so a crash mid-write can't corrupt the previous file
+pub fn write_atomic(file: &str, contents: &str) -> Result<(), SaveError> {
+    let temp_file = format!("{}.tmp", file);
+    std::fs::write(&temp_file, contents)?;
+    std::fs::rename(&temp_file, file)?;
+    Ok(())
+}
+
+pub fn unix_time() -> u64 {
+    std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .map_or(0, |duration| duration.as_secs())
+}
+
+// Called when the run is over, so there is nothing to continue from the menu
+pub fn delete() -> Result<(), SaveError> {
+    match std::fs::remove_file(SAVE_FILE) {
+        Err(err) if err.kind() != std::io::ErrorKind::NotFound => Err(err.into()),
+        _ => Ok(()),
+    }
+}
+
+// Upgrades older saves to the current format one version at a time
+fn migrate(mut value: serde_json::Value, version: u32) -> Result<serde_json::Value, SaveError> {
+    match version {
+        SAVE_VERSION => Ok(value),
+        // v2 added the player health and enemies
+        1 => {
+            value["health"] = serde_json::json!(MAX_HEALTH);
+            value["enemies"] = serde_json::json!([]);
+            migrate(value, 2)
+        }
+        // v3 added the abilities
+        2 => {
+            value["abilities"] = serde_json::json!([]);
+            value["gold_rush"] = serde_json::Value::Null;
+            migrate(value, 3)
+        }
+        // v4 added the auto-miners and the save time
+        3 => {
+            value["auto_miners"] = serde_json::json!([]);
+            value["saved_at"] = serde_json::json!(0);
+            migrate(value, 4)
+        }
+        // v5 added the deepest row reached, older runs start counting from where the player is
+        4 => {
+            value["deepest_row"] = serde_json::json!(0);
+            migrate(value, 5)
+        }
+        // v6 added the run time and start date for the run history
+        5 => {
+            value["run_time"] = serde_json::json!(0.0);
+            value["started_at"] = serde_json::json!(0);
+            migrate(value, 6)
+        }
+        // v7 added the game modes, older runs are endless ones
+        6 => {
+            value["mode"] = serde_json::to_value(GameMode::Endless)?;
+            migrate(value, 7)
+        }
+        // v8 kept the world above the screen, older saves threw it away
+        7 => {
+            value["chunks"] = serde_json::json!([]);
+            migrate(value, 8)
+        }
+        // v9 added the ladders
+        8 => {
+            value["ladders"] = serde_json::json!(0);
+            value["ladder_tiles"] = serde_json::json!([]);
+            migrate(value, 9)
+        }
+        // v10 added the backpack and its storage upgrades
+        9 => {
+            value["inventory"] = serde_json::json!([]);
+            value["storage_upgrades"] = serde_json::json!([]);
+            migrate(value, 10)
+        }
+        // v11 added the crafting queue
+        10 => {
+            value["crafting"] = serde_json::json!([]);
+            migrate(value, 11)
+        }
+        // v12 added the pickaxe durability, older pickaxes are as good as new
+        11 => {
+            value["durability"] = serde_json::json!(u32::MAX);
+            migrate(value, 12)
+        }
+        // v13 kept where the player walked to, older runs couldn't walk so they're in the middle
+        12 => {
+            value["player_x"] = serde_json::json!((WINDOW_SIZE_X as f32 - PLAYER_SIZE) / 2.0);
+            migrate(value, 13)
+        }
+        version if version > SAVE_VERSION => Err(SaveError::NewerVersion(version)),
+        version => Err(SaveError::UnsupportedVersion(version)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // What the very first save format looked like
+    const V1_SAVE: &str = r#"{
+        "version": 1,
+        "seed": 42,
+        "score": { "current_score": 120, "blocks_destroyed": 30, "gold": 55 },
+        "pickaxe": "normal",
+        "offset_y": -64.0,
+        "last_row": 40,
+        "blocks": [{ "column": 3, "row": 2, "block": "dirt", "hp": 20 }]
+    }"#;
+
+    #[test]
+    fn first_version_saves_migrate_to_the_current_one() {
+        let save = parse(V1_SAVE).unwrap();
+        assert_eq!(save.seed, 42);
+        assert_eq!(save.score.gold, 55);
+        assert_eq!(save.blocks.len(), 1);
+        assert_eq!(save.health, MAX_HEALTH);
+        assert_eq!(save.durability, u32::MAX);
+        assert_eq!(save.mode, GameMode::Endless);
+        assert_eq!(save.player_x, (WINDOW_SIZE_X as f32 - PLAYER_SIZE) / 2.0);
+        assert!(save.enemies.is_empty() && save.chunks.is_empty() && save.crafting.is_empty());
+    }
+
+    #[test]
+    fn saves_from_newer_versions_are_rejected() {
+        let newer = V1_SAVE.replace(
+            r#""version": 1"#,
+            &format!(r#""version": {}"#, SAVE_VERSION + 1),
+        );
+        assert!(matches!(
+            parse(&newer),
+            Err(SaveError::NewerVersion(version)) if version == SAVE_VERSION + 1
+        ));
+    }
+
+    #[test]
+    fn truncated_saves_are_corrupt() {
+        let truncated = &V1_SAVE[..V1_SAVE.len() / 2];
+        assert!(matches!(parse(truncated), Err(SaveError::Corrupt(_))));
+    }
+}
@@ -1,3 +1,4 @@
+#[derive(Clone, Debug)]
 pub struct Score {
     pub current_score: i32,
     pub blocks_destroyed: i32,
@@ -1,4 +1,7 @@
-use macroquad::{color::Color, math::Vec2};
+use macroquad::{
+    color::Color,
+    math::{vec2, Vec2},
+};
 
 pub struct Shape {
     pub size: Vec2,
@@ -6,3 +9,16 @@ pub struct Shape {
     pub y: f32,
     pub color: Color,
 }
+
+impl Shape {
+    pub fn center(&self) -> Vec2 {
+        vec2(self.x + self.size.x / 2.0, self.y + self.size.y / 2.0)
+    }
+
+    pub fn contains(&self, point: Vec2) -> bool {
+        point.x >= self.x
+            && point.x < self.x + self.size.x
+            && point.y >= self.y
+            && point.y < self.y + self.size.y
+    }
+}
@@ -0,0 +1,1900 @@
+use std::{
+    collections::{HashMap, VecDeque},
+    hash::Hasher,
+    rc::Rc,
+};
+
+use macroquad::prelude::*;
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    abilities::{Abilities, GoldRush},
+    auto_miners::AutoMiners,
+    biome::BiomeId,
+    block_registry::{BlockDef, BlockId},
+    crafting::Crafting,
+    definitions::Definitions,
+    enemy::Enemy,
+    enemy_registry::EnemyId,
+    inventory::Inventory,
+    items::ItemId,
+    modes::GameMode,
+    player::{
+        Facing, Player, CLIMB_SPEED, JUMP_SPEED, MAX_HEALTH, PLAYER_SIZE, WALK_SPEED,
+        WALL_CLIMB_SPEED,
+    },
+    profile::Bonuses,
+    recipes::{RecipeId, RecipeOutput},
+    rng::{Fnv1a, Rng},
+    save::{
+        SaveError, SaveGame, SavedAbility, SavedAutoMiner, SavedBlock, SavedChunk, SavedCraftJob,
+        SavedEnemy, SavedGoldRush, SavedItem, SavedScore, SAVE_VERSION,
+    },
+    score::Score,
+    shape::Shape,
+    terrain::Terrain,
+    upgrades::{AbilityEffect, AbilityId, AutoMinerId, PickaxeId, StorageUpgradeId},
+    world::{CompactChunk, Tile, World, CHUNK_ROWS},
+};
+
+const GRAVITY: f32 = 800.0;
+pub const BLOCK_SIZE: f32 = 32.0;
+const MAX_STEP_DISTANCE: f32 = BLOCK_SIZE / 4.0; // The player moves at most this much between two collision checks
+const MINING_REACH: f32 = BLOCK_SIZE * 2.5; // Max distance between the player and the center of a block to mine it
+const ENEMY_SIZE: f32 = BLOCK_SIZE * 0.75;
+const ENEMY_SEED_SALT: u64 = 0xE4E7; // So spawning enemies doesn't change the terrain of the row
+const PATH_RADIUS: i64 = 12; // Rows above and below the player that enemies can find their way through
+const AUTO_MINER_REACH: f32 = BLOCK_SIZE * 3.0; // Max distance between the player and the blocks the auto-miners hit
+const BASE_ROW: i64 = 1; // Standing on this row or above counts as being at the surface base
+pub const WORN_DURABILITY: f32 = 0.2; // Below this fraction of its durability the pickaxe is nearly broken
+const WORN_DAMAGE_MULTIPLIER: f32 = 0.5;
+
+// Everything the simulation needs to know about the player's input for a single frame
+#[derive(Debug, Default)]
+pub struct SimInput {
+    pub move_x: f32, // -1.0 left, 1.0 right
+    pub move_y: f32, // -1.0 up, 1.0 down, only does something while climbing
+    pub jump_pressed: bool,
+    pub aim: Option<Vec2>, // Cursor position in world coordinates
+    pub mine_pressed: bool,
+    pub commands: Vec<Command>,
+    // World y of the top of the screen, the world from there down is kept loaded when looking up
+    pub view_top: Option<f32>,
+}
+
+// One-off actions coming from the UI (shop, menus...)
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub enum Command {
+    BuyPickaxe(PickaxeId),
+    BuyAbility(AbilityId),
+    UseAbility(AbilityId),
+    BuyAutoMiner(AutoMinerId),
+    BuyLadders,
+    PlaceLadder, // In the tile the player is in
+    BuyStorageUpgrade(StorageUpgradeId),
+    SellItems, // Everything in the backpack
+    RepairPickaxe,
+    QueueRecipe(RecipeId),
+    Retire, // Ends the run on purpose
+}
+
+#[derive(Debug, PartialEq)]
+pub enum SoundEffect {
+    Pickaxe,
+}
+
+// Things that happened during an update, the frontend decides how to present them
+#[derive(Debug, PartialEq)]
+pub enum SimEvent {
+    BlockBroken { block_id: BlockId, x: f32, y: f32 },
+    BlockClicked(BlockId),
+    BlockDamaged { block_id: BlockId, damage: i32 },
+    SoundRequested(SoundEffect),
+    ScoreChanged,
+    PickaxeChanged(PickaxeId),
+    BiomeEntered(BiomeId),
+    EnemyKilled { enemy_id: EnemyId, x: f32, y: f32 },
+    PlayerHurt,
+    PlayerDied,
+    AbilityUsed(AbilityId),
+    GoldEarned(i32),
+    GoldSpent(i32),
+    PickaxeBroken(PickaxeId),
+    PickaxeRepaired(PickaxeId),
+    ItemPicked(ItemId),
+    InventoryFull(ItemId), // The item was left behind
+    ItemsSold(i32),        // Gold earned
+    RecipeCrafted(RecipeId),
+}
+
+// What the auto-miners earned while the game was closed
+#[derive(Debug)]
+pub struct OfflineReport {
+    pub seconds: f32,
+    pub blocks: i32,
+    pub gold: i32,
+}
+
+struct Params {
+    needed_x: i32,
+    block_area_top: f32,
+    last_row_y: f32,
+}
+
+// Headless game state, no macroquad window functions are called from here so it can run without a GPU
+pub struct Simulation {
+    pub seed: u64,
+    pub score: Score,
+    pub player: Player,
+    pub world: World,
+    pub enemies: Vec<Enemy>,
+    pub abilities: Abilities,
+    pub auto_miners: AutoMiners,
+    pub ladders: u32, // Bought but not placed yet
+    pub inventory: Inventory,
+    pub crafting: Crafting,
+    pub bonuses: Bonuses, // From the permanent prestige upgrades, set by whoever creates the simulation
+    pub current_biome: BiomeId,
+    pub deepest_row: i64,
+    pub run_time: f32,   // Seconds played in this run
+    pub started_at: u64, // Unix time in seconds, set by whoever creates the simulation. 0 means unknown
+    pub retired: bool,
+    pub enemies_enabled: bool, // Turned off by the game modes without enemies
+    pub shop_enabled: bool,    // Turned off by the daily digs without a shop, crafting goes with it
+    definitions: Rc<Definitions>,
+    terrain: Terrain,
+    params: Params,
+}
+
+impl Simulation {
+    pub fn new(viewport: Vec2, seed: u64, definitions: Rc<Definitions>) -> Self {
+        let player_shape = Shape {
+            x: (viewport.x - PLAYER_SIZE) / 2.0,
+            y: 0.0,
+            size: Vec2::splat(PLAYER_SIZE),
+            color: BLUE,
+        };
+
+        let score = Score::init();
+
+        let starting_pickaxe = definitions.upgrades.starting_pickaxe();
+        let durability = definitions.upgrades.pickaxe(starting_pickaxe).durability;
+        let player = Player::new(player_shape, starting_pickaxe, durability);
+
+        let needed_x = (viewport.x / BLOCK_SIZE).ceil() as i32;
+        let block_area_top = viewport.y / 2.0;
+        let last_row_y = block_area_top;
+
+        Self {
+            seed,
+            score,
+            player,
+            world: World::new(needed_x, block_area_top),
+            enemies: Vec::new(),
+            abilities: Abilities::new(&definitions.upgrades),
+            ladders: 0,
+            inventory: Inventory::new(&definitions.items, &definitions.upgrades),
+            crafting: Crafting::new(),
+            auto_miners: AutoMiners::new(&definitions.upgrades),
+            bonuses: Bonuses::default(),
+            current_biome: definitions.biomes.biome_at(0),
+            deepest_row: 0,
+            run_time: 0.0,
+            started_at: 0,
+            retired: false,
+            enemies_enabled: true,
+            shop_enabled: true,
+            definitions,
+            terrain: Terrain::new(seed),
+            params: Params {
+                needed_x,
+                block_area_top,
+                last_row_y,
+            },
+        }
+    }
+
+    pub fn update(&mut self, input: &SimInput, delta_time: f32, viewport: Vec2) -> Vec<SimEvent> {
+        let mut events = Vec::new();
+        self.store_previous_positions();
+
+        // Nothing happens anymore once the player is dead
+        if self.is_game_over() {
+            return events;
+        }
+
+        self.run_time += delta_time;
+        // Fast falls and long frames are split in smaller steps, so the player can't go through a row of
+        // blocks in between two collision checks
+        let max_speed = (self.player.speed.abs() + GRAVITY * self.bonuses.fall_speed * delta_time)
+            .max(JUMP_SPEED)
+            .max(WALK_SPEED);
+        let steps = (max_speed * delta_time / MAX_STEP_DISTANCE).ceil().max(1.0) as u32;
+        let step_time = delta_time / steps as f32;
+        for step in 0..steps {
+            if !self.apply_climbing(input, step_time) {
+                if step == 0 {
+                    self.apply_jump(input);
+                }
+                self.apply_gravity(step_time);
+            }
+            self.apply_walking(input, step_time);
+            self.update_player_position();
+            self.update_depth(&mut events);
+            self.update_blocks(viewport);
+            self.update_chunks(input.view_top, viewport);
+            self.player_collision();
+        }
+        self.update_enemies(delta_time, &mut events);
+        self.abilities.update(delta_time);
+        self.update_crafting(delta_time, &mut events);
+        self.update_auto_miners(delta_time, &mut events);
+        self.handle_input(input, &mut events);
+
+        events
+    }
+
+    //
+    // SAVE FUNCTIONS
+    //
+
+    pub fn to_save(&self) -> SaveGame {
+        let blocks = self
+            .world
+            .blocks()
+            .map(|((column, row), block)| SavedBlock {
+                column,
+                row,
+                block: self.definitions.blocks.get(block.block_id).id.clone(),
+                hp: block.hp,
+            })
+            .collect();
+
+        SaveGame {
+            version: SAVE_VERSION,
+            seed: self.seed,
+            score: SavedScore {
+                current_score: self.score.current_score,
+                blocks_destroyed: self.score.blocks_destroyed,
+                gold: self.score.gold,
+            },
+            pickaxe: self
+                .definitions
+                .upgrades
+                .pickaxe(self.player.current_pickaxe)
+                .id
+                .clone(),
+            durability: self.player.durability,
+            player_x: self.player.shape.x,
+            offset_y: self.player.offset_y,
+            last_row: self.row_of(self.params.last_row_y),
+            blocks,
+            chunks: self
+                .world
+                .evicted()
+                .map(|(chunk, compact)| self.save_chunk(chunk, compact))
+                .collect(),
+            health: self.player.health,
+            enemies: self
+                .enemies
+                .iter()
+                .map(|enemy| SavedEnemy {
+                    enemy: self.definitions.enemies.get(enemy.enemy_id).id.clone(),
+                    x: enemy.shape.x,
+                    y: enemy.shape.y - self.params.block_area_top,
+                    hp: enemy.hp,
+                })
+                .collect(),
+            abilities: self
+                .definitions
+                .upgrades
+                .abilities()
+                .filter(|(ability_id, _)| self.abilities.is_owned(*ability_id))
+                .map(|(ability_id, ability)| SavedAbility {
+                    ability: ability.id.clone(),
+                    cooldown: self.abilities.cooldown(ability_id),
+                })
+                .collect(),
+            gold_rush: self.abilities.gold_rush.map(|gold_rush| SavedGoldRush {
+                remaining: gold_rush.remaining,
+                multiplier: gold_rush.multiplier,
+            }),
+            auto_miners: self
+                .definitions
+                .upgrades
+                .auto_miners()
+                .filter(|(auto_miner_id, _)| self.auto_miners.count(*auto_miner_id) > 0)
+                .map(|(auto_miner_id, auto_miner)| SavedAutoMiner {
+                    auto_miner: auto_miner.id.clone(),
+                    count: self.auto_miners.count(auto_miner_id),
+                })
+                .collect(),
+            inventory: self
+                .definitions
+                .items
+                .iter()
+                .filter(|(item_id, _)| self.inventory.count(*item_id) > 0)
+                .map(|(item_id, item)| SavedItem {
+                    item: item.id.clone(),
+                    count: self.inventory.count(item_id),
+                })
+                .collect(),
+            storage_upgrades: self
+                .definitions
+                .upgrades
+                .storage_upgrades()
+                .filter(|(upgrade_id, _)| self.inventory.owns_storage(*upgrade_id))
+                .map(|(_, upgrade)| upgrade.id.clone())
+                .collect(),
+            crafting: self
+                .crafting
+                .jobs()
+                .map(|job| SavedCraftJob {
+                    recipe: self.definitions.recipes.get(job.recipe).id.clone(),
+                    elapsed: job.elapsed,
+                })
+                .collect(),
+            ladders: self.ladders,
+            ladder_tiles: self.world.ladders().collect(),
+            saved_at: 0,
+            deepest_row: self.deepest_row,
+            run_time: self.run_time,
+            started_at: self.started_at,
+            mode: GameMode::default(), // The rules live in the frontend, it fills this in
+        }
+    }
+
+    pub fn from_save(
+        save: &SaveGame,
+        viewport: Vec2,
+        definitions: Rc<Definitions>,
+    ) -> Result<Self, SaveError> {
+        let mut simulation = Self::new(viewport, save.seed, definitions);
+
+        simulation.score.current_score = save.score.current_score;
+        simulation.score.blocks_destroyed = save.score.blocks_destroyed;
+        simulation.score.gold = save.score.gold;
+
+        simulation.player.current_pickaxe = simulation
+            .definitions
+            .upgrades
+            .find_pickaxe(&save.pickaxe)
+            .ok_or_else(|| SaveError::Invalid(format!("unknown pickaxe '{}'", save.pickaxe)))?;
+        simulation.player.durability = save.durability.min(simulation.max_durability());
+        simulation.player.shape.x = save
+            .player_x
+            .clamp(0.0, viewport.x - simulation.player.shape.size.x);
+        simulation.player.offset_y = save.offset_y;
+        simulation.player.speed = 0.0;
+        simulation.params.last_row_y = simulation.row_y(save.last_row);
+
+        // Older saves still have the blocks that were dug out
+        for saved_block in save.blocks.iter().filter(|saved_block| saved_block.hp > 0) {
+            let block_id = simulation
+                .definitions
+                .blocks
+                .find(&saved_block.block)
+                .ok_or_else(|| {
+                    SaveError::Invalid(format!("unknown block '{}'", saved_block.block))
+                })?;
+            let def = simulation.definitions.blocks.get(block_id);
+
+            let tile = (saved_block.column, saved_block.row);
+            if let Some(block) = simulation.world.place(tile, block_id, def) {
+                block.hp = saved_block.hp.min(def.base_hp);
+            }
+        }
+
+        for saved_chunk in &save.chunks {
+            simulation.load_chunk(saved_chunk)?;
+        }
+
+        for saved_enemy in &save.enemies {
+            let enemy_id = simulation
+                .definitions
+                .enemies
+                .find(&saved_enemy.enemy)
+                .ok_or_else(|| {
+                    SaveError::Invalid(format!("unknown enemy '{}'", saved_enemy.enemy))
+                })?;
+            let def = simulation.definitions.enemies.get(enemy_id);
+
+            let shape = Self::enemy_shape(
+                vec2(
+                    saved_enemy.x,
+                    saved_enemy.y + simulation.params.block_area_top,
+                ),
+                def.color,
+            );
+            let mut enemy = Enemy::new(shape, enemy_id, def);
+            enemy.hp = saved_enemy.hp.clamp(1, def.hp);
+            simulation.enemies.push(enemy);
+        }
+
+        for saved_ability in &save.abilities {
+            let ability_id = simulation
+                .definitions
+                .upgrades
+                .find_ability(&saved_ability.ability)
+                .ok_or_else(|| {
+                    SaveError::Invalid(format!("unknown ability '{}'", saved_ability.ability))
+                })?;
+            simulation.abilities.unlock(ability_id);
+            simulation
+                .abilities
+                .set_cooldown(ability_id, saved_ability.cooldown);
+        }
+        simulation.abilities.gold_rush = save.gold_rush.as_ref().map(|gold_rush| GoldRush {
+            remaining: gold_rush.remaining,
+            multiplier: gold_rush.multiplier,
+        });
+
+        for saved_auto_miner in &save.auto_miners {
+            let auto_miner_id = simulation
+                .definitions
+                .upgrades
+                .find_auto_miner(&saved_auto_miner.auto_miner)
+                .ok_or_else(|| {
+                    SaveError::Invalid(format!(
+                        "unknown auto-miner '{}'",
+                        saved_auto_miner.auto_miner
+                    ))
+                })?;
+            simulation
+                .auto_miners
+                .set_count(auto_miner_id, saved_auto_miner.count);
+        }
+
+        for saved_item in &save.inventory {
+            let item_id = simulation
+                .definitions
+                .items
+                .find(&saved_item.item)
+                .ok_or_else(|| SaveError::Invalid(format!("unknown item '{}'", saved_item.item)))?;
+            simulation.inventory.set_count(item_id, saved_item.count);
+        }
+        for storage_upgrade in &save.storage_upgrades {
+            let upgrade_id = simulation
+                .definitions
+                .upgrades
+                .find_storage_upgrade(storage_upgrade)
+                .ok_or_else(|| {
+                    SaveError::Invalid(format!("unknown storage upgrade '{}'", storage_upgrade))
+                })?;
+            simulation.inventory.unlock_storage(upgrade_id);
+        }
+
+        for saved_job in &save.crafting {
+            let recipe_id = simulation
+                .definitions
+                .recipes
+                .find(&saved_job.recipe)
+                .ok_or_else(|| {
+                    SaveError::Invalid(format!("unknown recipe '{}'", saved_job.recipe))
+                })?;
+            simulation.crafting.push(recipe_id, saved_job.elapsed);
+        }
+
+        simulation.ladders = save.ladders;
+        for &tile in &save.ladder_tiles {
+            simulation.world.place_ladder(tile);
+        }
+
+        // A run that was still going can't have been saved with a dead player
+        simulation.player.health = save.health.clamp(1, MAX_HEALTH);
+        simulation.deepest_row = save.deepest_row;
+        simulation.run_time = save.run_time;
+        simulation.started_at = save.started_at;
+        simulation.update_player_position();
+        simulation.update_chunks(None, viewport);
+        simulation.store_previous_positions();
+        simulation.current_biome = simulation
+            .definitions
+            .biomes
+            .biome_at(simulation.row_of(simulation.player.shape.y));
+        Ok(simulation)
+    }
+
+    // The tiles are stored by the index of the block id in the chunk, so a save still works if blocks are
+    // added or moved around in the definitions
+    fn save_chunk(&self, chunk: i64, compact: &CompactChunk) -> SavedChunk {
+        let mut palette: Vec<u16> = compact
+            .blocks
+            .iter()
+            .copied()
+            .filter(|&block| block != 0)
+            .collect();
+        palette.sort_unstable();
+        palette.dedup();
+
+        SavedChunk {
+            chunk,
+            width: self.params.needed_x,
+            blocks: palette
+                .iter()
+                .map(|&block| {
+                    let block_id = BlockId(block as usize - 1);
+                    self.definitions.blocks.get(block_id).id.clone()
+                })
+                .collect(),
+            tiles: compact
+                .blocks
+                .iter()
+                .map(|&block| {
+                    palette
+                        .iter()
+                        .position(|&other| other == block)
+                        .map_or(0, |index| index as u16 + 1)
+                })
+                .collect(),
+            damaged: compact.damaged.clone(),
+        }
+    }
+
+    // Chunks are loaded as normal blocks and packed away again once the player is in place
+    fn load_chunk(&mut self, saved_chunk: &SavedChunk) -> Result<(), SaveError> {
+        if saved_chunk.width != self.params.needed_x {
+            return Err(SaveError::Invalid(format!(
+                "chunk {} was saved with {} columns, the world has {}",
+                saved_chunk.chunk, saved_chunk.width, self.params.needed_x
+            )));
+        }
+        let tile_count = saved_chunk.width as usize * CHUNK_ROWS as usize;
+        if saved_chunk.tiles.len() != tile_count {
+            return Err(SaveError::Invalid(format!(
+                "chunk {} has {} tiles instead of {}",
+                saved_chunk.chunk,
+                saved_chunk.tiles.len(),
+                tile_count
+            )));
+        }
+        if let Some(&(index, _)) = saved_chunk
+            .damaged
+            .iter()
+            .find(|(index, _)| *index as usize >= tile_count)
+        {
+            return Err(SaveError::Invalid(format!(
+                "chunk {} has a damaged block outside of it ({})",
+                saved_chunk.chunk, index
+            )));
+        }
+
+        let block_ids = saved_chunk
+            .blocks
+            .iter()
+            .map(|block| {
+                self.definitions
+                    .blocks
+                    .find(block)
+                    .ok_or_else(|| SaveError::Invalid(format!("unknown block '{}'", block)))
+            })
+            .collect::<Result<Vec<BlockId>, SaveError>>()?;
+
+        let width = saved_chunk.width as i64;
+        let tile_of = |index: i64| {
+            (
+                (index % width) as i32,
+                saved_chunk.chunk * CHUNK_ROWS + index / width,
+            )
+        };
+        for (index, &block) in saved_chunk.tiles.iter().enumerate() {
+            if block == 0 {
+                continue;
+            }
+            let block_id = *block_ids.get(block as usize - 1).ok_or_else(|| {
+                SaveError::Invalid(format!("chunk {} has an unknown tile", saved_chunk.chunk))
+            })?;
+            let def = self.definitions.blocks.get(block_id);
+            self.world.place(tile_of(index as i64), block_id, def);
+        }
+        for &(index, hp) in &saved_chunk.damaged {
+            if let Some(block) = self.world.get_mut(tile_of(index as i64)) {
+                let def = self.definitions.blocks.get(block.block_id);
+                block.hp = hp.clamp(1, def.base_hp);
+            }
+        }
+        Ok(())
+    }
+
+    // Rough estimate of what the auto-miners would have dug while the game was closed, they are assumed
+    // to be digging the fill block of the current biome the whole time and selling whatever it drops
+    pub fn apply_offline_progress(
+        &mut self,
+        elapsed_seconds: f32,
+        events: &mut Vec<SimEvent>,
+    ) -> Option<OfflineReport> {
+        let offline = &self.definitions.upgrades.offline;
+        let seconds = elapsed_seconds.clamp(0.0, offline.max_hours * 3600.0);
+
+        let fill_id = self.definitions.biomes.get(self.current_biome).fill;
+        let fill = self.definitions.blocks.get(fill_id);
+        let damage_per_second: f32 = self
+            .definitions
+            .upgrades
+            .auto_miners()
+            .map(|(auto_miner_id, auto_miner)| {
+                let damage = Self::damage_against(auto_miner.damage, 0, fill);
+                self.auto_miners.count(auto_miner_id) as f32 * damage as f32 / auto_miner.interval
+            })
+            .sum();
+
+        let blocks =
+            (damage_per_second * seconds * offline.efficiency / fill.base_hp as f32).floor() as i32;
+        if blocks <= 0 {
+            return None;
+        }
+
+        let gold_per_block = match self.definitions.items.drop_of(fill_id) {
+            Some(item_id) => self.definitions.items.get(item_id).price,
+            None => fill.gold,
+        };
+        self.score.blocks_destroyed += blocks;
+        self.score.current_score += blocks * fill.score;
+        self.score.gold += blocks * gold_per_block;
+        events.push(SimEvent::GoldEarned(blocks * gold_per_block));
+        events.push(SimEvent::ScoreChanged);
+        Some(OfflineReport {
+            seconds,
+            blocks,
+            gold: blocks * gold_per_block,
+        })
+    }
+
+    //
+    // UPDATE FUNCTIONS
+    //
+
+    fn store_previous_positions(&mut self) {
+        self.player.previous_position = vec2(self.player.shape.x, self.player.shape.y);
+        for enemy in &mut self.enemies {
+            enemy.previous_position = vec2(enemy.shape.x, enemy.shape.y);
+        }
+    }
+
+    fn apply_gravity(&mut self, delta_time: f32) {
+        self.player.speed += GRAVITY * self.bonuses.fall_speed * delta_time;
+        self.player.offset_y -= self.player.speed * delta_time;
+    }
+
+    // Ladders can be climbed both ways, the walls of a shaft only up and slower. Returns false when the
+    // player isn't climbing, so gravity takes over
+    fn apply_climbing(&mut self, input: &SimInput, delta_time: f32) -> bool {
+        let move_y = input.move_y.clamp(-1.0, 1.0);
+        let climb_speed = if self.is_on_ladder() {
+            CLIMB_SPEED
+        } else if move_y < 0.0 && self.is_against_wall(input.move_x) {
+            WALL_CLIMB_SPEED
+        } else {
+            self.player.climbing = false;
+            return false;
+        };
+
+        self.player.climbing = true;
+        self.player.speed = 0.0;
+        self.player.offset_y -= move_y * climb_speed * delta_time;
+        true
+    }
+
+    fn apply_jump(&mut self, input: &SimInput) {
+        if input.jump_pressed && self.player.grounded {
+            self.player.speed = -JUMP_SPEED;
+        }
+    }
+
+    fn apply_walking(&mut self, input: &SimInput, delta_time: f32) {
+        let move_x = input.move_x.clamp(-1.0, 1.0);
+        // Pushing into the wall while climbing it would catch the corner of the block above
+        if !(self.player.climbing && self.is_against_wall(move_x)) {
+            self.player.shape.x += move_x * WALK_SPEED * delta_time;
+        }
+
+        // Don't let the player walk out of the world
+        let max_x = self.world_width() - self.player.shape.size.x;
+        self.player.shape.x = self.player.shape.x.clamp(0.0, max_x);
+
+        // You face the way you walk, if you are standing still you dig down
+        self.player.facing = if move_x < 0.0 {
+            Facing::Left
+        } else if move_x > 0.0 {
+            Facing::Right
+        } else {
+            Facing::Down
+        };
+    }
+
+    fn update_player_position(&mut self) {
+        // The drawing function draws from the top-left corner
+        //player.shape.y = block_area_top - player.shape.size.y; // The player should be above the blocks sowe subtract the player size.
+        self.player.shape.y =
+            self.params.block_area_top - self.player.shape.size.y - self.player.offset_y;
+        // Take into account the gravity offset
+    }
+
+    fn update_depth(&mut self, events: &mut Vec<SimEvent>) {
+        let row = self.row_of(self.player.shape.y + self.player.shape.size.y);
+        self.deepest_row = self.deepest_row.max(row);
+
+        let biome = self.definitions.biomes.biome_at(row);
+        if biome != self.current_biome {
+            self.current_biome = biome;
+            events.push(SimEvent::BiomeEntered(biome));
+        }
+    }
+
+    fn update_blocks(&mut self, viewport: Vec2) {
+        // Generate new blocks if needed
+        if self.player.shape.y + viewport.y > self.params.last_row_y {
+            let new_row_y = self.params.last_row_y + BLOCK_SIZE;
+            let row = self.row_of(new_row_y);
+            let cave_columns = Self::spawn_row_of_blocks(
+                &mut self.world,
+                &self.definitions,
+                &self.terrain,
+                self.params.needed_x,
+                row,
+                new_row_y,
+                &mut Rng::for_row(self.seed, row),
+            );
+            if self.enemies_enabled {
+                Self::spawn_enemies(
+                    &mut self.enemies,
+                    &self.definitions,
+                    &cave_columns,
+                    row,
+                    new_row_y,
+                    &mut Rng::for_row(self.seed ^ ENEMY_SEED_SALT, row),
+                );
+            }
+            self.params.last_row_y = new_row_y;
+        }
+    }
+
+    // Chunks more than a screen above the player (or above what's on screen) are packed away, and unpacked
+    // again once the player or the camera gets back to them
+    fn update_chunks(&mut self, view_top: Option<f32>, viewport: Vec2) {
+        let mut top = self.player.shape.y - viewport.y;
+        if let Some(view_top) = view_top {
+            top = top.min(view_top);
+        }
+        let first_row = self.row_of(top);
+        self.world.evict_above(first_row, &self.definitions.blocks);
+        self.world.restore_between(
+            first_row,
+            self.row_of(self.params.last_row_y),
+            &self.definitions.blocks,
+        );
+
+        self.enemies.retain(|enemy| enemy.shape.y > top);
+    }
+
+    fn player_collision(&mut self) {
+        // Only the tiles around the player can touch them, one extra tile on every side in case a push
+        // moves the player into the next one
+        let hitbox = self.player.hitbox();
+        let margin = Vec2::splat(BLOCK_SIZE);
+        let tiles: Vec<Tile> = self
+            .world
+            .tiles_between(
+                vec2(hitbox.x, hitbox.y) - margin,
+                vec2(hitbox.x + hitbox.size.x, hitbox.y + hitbox.size.y) + margin,
+            )
+            .collect();
+
+        // Collision detection and resolution
+        self.player.grounded = false;
+        for tile in tiles {
+            if let Some(block) = self.world.get(tile) {
+                if Self::check_collision(&self.player.hitbox(), &block.shape) {
+                    Self::resolve_collision(
+                        &mut self.player,
+                        &block.shape,
+                        self.params.block_area_top,
+                    );
+                }
+            }
+        }
+    }
+
+    // Enemies walk from tile center to tile center following the shortest path through open tiles,
+    // once they are in the same tile as the player they go straight for them
+    fn update_enemies(&mut self, delta_time: f32, events: &mut Vec<SimEvent>) {
+        self.player.hurt_timer = (self.player.hurt_timer - delta_time).max(0.0);
+        if self.enemies.is_empty() {
+            return;
+        }
+
+        let distances = self.path_distances();
+        let world = &self.world;
+        let block_area_top = self.params.block_area_top;
+        let player_hitbox = self.player.hitbox();
+        let player_center = player_hitbox.center();
+
+        for enemy in &mut self.enemies {
+            let def = self.definitions.enemies.get(enemy.enemy_id);
+
+            let center = enemy.shape.center();
+            if center.distance(enemy.target) < 0.5 {
+                let tile = world.tile_at(center);
+                match distances.get(&tile) {
+                    Some(0) => enemy.target = player_center,
+                    Some(&distance) => {
+                        let next_tile = Self::neighbour_tiles(tile)
+                            .into_iter()
+                            .filter_map(|next| distances.get(&next).map(|d| (next, *d)))
+                            .filter(|(_, next_distance)| *next_distance < distance)
+                            .min_by_key(|(_, next_distance)| *next_distance);
+                        if let Some((next_tile, _)) = next_tile {
+                            enemy.target = Self::tile_center(next_tile, block_area_top);
+                        }
+                    }
+                    // Too far away or walled off, wait until the player digs a way in
+                    None => {}
+                }
+            }
+            enemy.move_towards(enemy.target, def.speed * delta_time);
+
+            if Self::check_collision(&player_hitbox, &enemy.shape)
+                && self.player.take_damage(def.damage)
+            {
+                events.push(SimEvent::PlayerHurt);
+                if self.player.is_dead() {
+                    events.push(SimEvent::PlayerDied);
+                    return;
+                }
+            }
+        }
+    }
+
+    // Made items wait at the front of the queue until there's room for them in the backpack
+    fn update_crafting(&mut self, delta_time: f32, events: &mut Vec<SimEvent>) {
+        let Some(recipe_id) = self.crafting.update(delta_time, &self.definitions.recipes) else {
+            return;
+        };
+
+        match self.definitions.recipes.get(recipe_id).output {
+            RecipeOutput::Item { item, count } => {
+                let items = &self.definitions.items;
+                if !self
+                    .inventory
+                    .add(item, count, items, &self.definitions.upgrades)
+                {
+                    return;
+                }
+            }
+            RecipeOutput::Pickaxe(pickaxe) => {
+                // Another pickaxe from the queue might have been better
+                let upgrades = &self.definitions.upgrades;
+                if upgrades.pickaxe(pickaxe).tier
+                    > upgrades.pickaxe(self.player.current_pickaxe).tier
+                {
+                    self.player.current_pickaxe = pickaxe;
+                    self.player.durability = upgrades.pickaxe(pickaxe).durability;
+                    events.push(SimEvent::PickaxeChanged(pickaxe));
+                }
+            }
+            RecipeOutput::StorageUpgrade(upgrade) => self.inventory.unlock_storage(upgrade),
+        }
+
+        self.crafting.finish();
+        events.push(SimEvent::RecipeCrafted(recipe_id));
+    }
+
+    // Every helper hits a different block, starting with the closest one to the player
+    fn update_auto_miners(&mut self, delta_time: f32, events: &mut Vec<SimEvent>) {
+        let definitions = self.definitions.clone();
+        for (auto_miner_id, auto_miner) in definitions.upgrades.auto_miners() {
+            if !self
+                .auto_miners
+                .tick(auto_miner_id, delta_time, auto_miner.interval)
+            {
+                continue;
+            }
+
+            let count = self.auto_miners.count(auto_miner_id) as usize;
+            for tile in self.auto_miner_targets(count) {
+                let Some(block) = self.world.get(tile) else {
+                    continue;
+                };
+                let def = definitions.blocks.get(block.block_id);
+                let damage = Self::damage_against(auto_miner.damage, 0, def);
+                self.damage_block(tile, damage, events);
+            }
+        }
+    }
+
+    fn handle_input(&mut self, input: &SimInput, events: &mut Vec<SimEvent>) {
+        if input.mine_pressed {
+            events.push(SimEvent::SoundRequested(SoundEffect::Pickaxe));
+            // Fighting back comes before digging
+            if !self.hit_enemy(input.aim, events) {
+                self.mine_target_block(input.aim, events);
+            }
+        }
+
+        let is_at_base = self.is_at_base();
+        for command in &input.commands {
+            match command {
+                // The shop is at the surface base, if the mode has one
+                Command::BuyPickaxe(_)
+                | Command::BuyAbility(_)
+                | Command::BuyAutoMiner(_)
+                | Command::BuyLadders
+                | Command::BuyStorageUpgrade(_)
+                | Command::SellItems
+                | Command::RepairPickaxe
+                    if !is_at_base || !self.shop_enabled => {}
+                Command::QueueRecipe(_) if !self.shop_enabled => {}
+                Command::BuyPickaxe(pickaxe) => {
+                    let blocker = self.definitions.upgrades.pickaxe_blocker(
+                        self.player.current_pickaxe,
+                        *pickaxe,
+                        self.score.gold,
+                    );
+                    if blocker.is_none() {
+                        let price = self.definitions.upgrades.pickaxe(*pickaxe).price;
+                        self.player.current_pickaxe = *pickaxe;
+                        self.player.durability = self.max_durability();
+                        self.score.gold -= price;
+                        events.push(SimEvent::PickaxeChanged(*pickaxe));
+                        events.push(SimEvent::GoldSpent(price));
+                        events.push(SimEvent::ScoreChanged);
+                    }
+                }
+                Command::BuyAbility(ability) => {
+                    let blocker = self.definitions.upgrades.ability_blocker(
+                        self.abilities.is_owned(*ability),
+                        *ability,
+                        self.score.gold,
+                    );
+                    if blocker.is_none() {
+                        let price = self.definitions.upgrades.ability(*ability).price;
+                        self.abilities.unlock(*ability);
+                        self.score.gold -= price;
+                        events.push(SimEvent::GoldSpent(price));
+                        events.push(SimEvent::ScoreChanged);
+                    }
+                }
+                Command::UseAbility(ability) => {
+                    if self.abilities.is_ready(*ability) {
+                        self.use_ability(*ability, events);
+                    }
+                }
+                Command::BuyAutoMiner(auto_miner) => {
+                    let price = self
+                        .definitions
+                        .upgrades
+                        .auto_miner(*auto_miner)
+                        .price(self.auto_miners.count(*auto_miner));
+                    if self.score.gold >= price {
+                        self.auto_miners
+                            .set_count(*auto_miner, self.auto_miners.count(*auto_miner) + 1);
+                        self.score.gold -= price;
+                        events.push(SimEvent::GoldSpent(price));
+                        events.push(SimEvent::ScoreChanged);
+                    }
+                }
+                Command::BuyLadders => {
+                    let ladders = &self.definitions.upgrades.ladders;
+                    if self.score.gold >= ladders.price {
+                        self.ladders += ladders.bundle;
+                        self.score.gold -= ladders.price;
+                        events.push(SimEvent::GoldSpent(ladders.price));
+                        events.push(SimEvent::ScoreChanged);
+                    }
+                }
+                Command::PlaceLadder => {
+                    let tile = self.world.tile_at(self.player.hitbox().center());
+                    if self.ladders > 0 && tile.1 >= BASE_ROW && self.world.place_ladder(tile) {
+                        self.ladders -= 1;
+                    }
+                }
+                Command::BuyStorageUpgrade(upgrade) => {
+                    let blocker = self.definitions.upgrades.storage_blocker(
+                        self.inventory.owned_storage(),
+                        *upgrade,
+                        self.score.gold,
+                    );
+                    if blocker.is_none() {
+                        let price = self.definitions.upgrades.storage_upgrade(*upgrade).price;
+                        self.inventory.unlock_storage(*upgrade);
+                        self.score.gold -= price;
+                        events.push(SimEvent::GoldSpent(price));
+                        events.push(SimEvent::ScoreChanged);
+                    }
+                }
+                Command::SellItems => {
+                    if !self.inventory.is_empty() {
+                        let gold = self.sell_value();
+                        self.inventory.clear();
+                        self.score.gold += gold;
+                        events.push(SimEvent::ItemsSold(gold));
+                        events.push(SimEvent::GoldEarned(gold));
+                        events.push(SimEvent::ScoreChanged);
+                    }
+                }
+                Command::RepairPickaxe => {
+                    let price = self.repair_price();
+                    if self.player.durability < self.max_durability() && self.score.gold >= price {
+                        self.player.durability = self.max_durability();
+                        self.score.gold -= price;
+                        events.push(SimEvent::PickaxeRepaired(self.player.current_pickaxe));
+                        events.push(SimEvent::GoldSpent(price));
+                        events.push(SimEvent::ScoreChanged);
+                    }
+                }
+                Command::QueueRecipe(recipe) => {
+                    if self.craft_blocker(*recipe).is_none() {
+                        for &(item, count) in &self.definitions.recipes.get(*recipe).ingredients {
+                            self.inventory.remove(item, count);
+                        }
+                        self.crafting.push(*recipe, 0.0);
+                    }
+                }
+                Command::Retire => self.retired = true,
+            }
+        }
+    }
+
+    //
+    // HELPERS
+    //
+
+    // Row 0 is the surface line, so the first row of blocks is row 1
+    fn row_of(&self, y: f32) -> i64 {
+        ((y - self.params.block_area_top) / BLOCK_SIZE).round() as i64
+    }
+
+    fn row_y(&self, row: i64) -> f32 {
+        self.params.block_area_top + row as f32 * BLOCK_SIZE
+    }
+
+    // Returns the columns of the row that are part of a cave
+    fn spawn_row_of_blocks(
+        world: &mut World,
+        definitions: &Definitions,
+        terrain: &Terrain,
+        needed_x: i32,
+        row: i64,
+        y: f32,
+        rng: &mut Rng,
+    ) -> Vec<i32> {
+        let mut cave_columns = Vec::new();
+        for x in 0..needed_x {
+            // Caves don't get a block at all
+            let Some(block_id) = terrain.block_at(definitions, x, row, y, rng) else {
+                cave_columns.push(x);
+                continue;
+            };
+
+            world.place((x, row), block_id, definitions.blocks.get(block_id));
+        }
+        cave_columns
+    }
+
+    // Every cave cell rolls once, the enemies that can live at this depth split the roll by their spawn chance
+    fn spawn_enemies(
+        enemies: &mut Vec<Enemy>,
+        definitions: &Definitions,
+        cave_columns: &[i32],
+        row: i64,
+        y: f32,
+        rng: &mut Rng,
+    ) {
+        for &column in cave_columns {
+            let roll = rng.next_f32();
+            let mut chance = 0.0;
+            for (enemy_id, def) in definitions.enemies.iter() {
+                if !def.can_spawn_at(row) {
+                    continue;
+                }
+
+                chance += def.spawn_chance;
+                if roll < chance {
+                    let offset = (BLOCK_SIZE - ENEMY_SIZE) / 2.0;
+                    let shape = Self::enemy_shape(
+                        vec2(column as f32 * BLOCK_SIZE + offset, y + offset),
+                        def.color,
+                    );
+                    enemies.push(Enemy::new(shape, enemy_id, def));
+                    break;
+                }
+            }
+        }
+    }
+
+    fn enemy_shape(position: Vec2, [r, g, b]: [f32; 3]) -> Shape {
+        Shape {
+            x: position.x,
+            y: position.y,
+            size: Vec2::splat(ENEMY_SIZE),
+            color: Color::new(r, g, b, 1.0),
+        }
+    }
+
+    fn tile_center((column, row): Tile, block_area_top: f32) -> Vec2 {
+        vec2(
+            (column as f32 + 0.5) * BLOCK_SIZE,
+            block_area_top + (row as f32 + 0.5) * BLOCK_SIZE,
+        )
+    }
+
+    fn neighbour_tiles((column, row): Tile) -> [Tile; 4] {
+        [
+            (column - 1, row),
+            (column + 1, row),
+            (column, row - 1),
+            (column, row + 1),
+        ]
+    }
+
+    // Distance in tiles from every open tile around the player to the player (breadth first search)
+    fn path_distances(&self) -> HashMap<Tile, u32> {
+        let last_row = self.row_of(self.params.last_row_y);
+
+        let start = self.world.tile_at(self.player.hitbox().center());
+        let mut distances = HashMap::from([(start, 0)]);
+        let mut queue = VecDeque::from([start]);
+        while let Some(tile) = queue.pop_front() {
+            let distance = distances[&tile];
+            for next in Self::neighbour_tiles(tile) {
+                let is_open = (0..self.params.needed_x).contains(&next.0)
+                    && next.1 <= last_row
+                    && (next.1 - start.1).abs() <= PATH_RADIUS
+                    && !self.world.is_solid(next);
+                if is_open && !distances.contains_key(&next) {
+                    distances.insert(next, distance + 1);
+                    queue.push_back(next);
+                }
+            }
+        }
+        distances
+    }
+
+    fn check_collision(a: &Shape, b: &Shape) -> bool {
+        a.x < b.x + b.size.x && a.x + a.size.x > b.x && a.y < b.y + b.size.y && a.y + a.size.y > b.y
+    }
+
+    // Pushes the player out of the block along the axis where they overlap the least
+    fn resolve_collision(player: &mut Player, block: &Shape, block_area_top: f32) {
+        let hitbox = player.hitbox();
+        let overlap_x = (hitbox.x + hitbox.size.x - block.x).min(block.x + block.size.x - hitbox.x);
+        let overlap_y = (hitbox.y + hitbox.size.y - block.y).min(block.y + block.size.y - hitbox.y);
+
+        if overlap_y <= overlap_x {
+            if hitbox.center().y < block.center().y {
+                // Standing on top of the block
+                player.offset_y = block_area_top - block.y;
+                player.speed = 0.0;
+                player.grounded = true;
+            } else {
+                // Bumping the head against the block
+                let block_bottom = block.y + block.size.y;
+                player.offset_y = block_area_top - player.shape.size.y - block_bottom;
+                player.speed = player.speed.max(0.0);
+            }
+            player.shape.y = block_area_top - player.shape.size.y - player.offset_y;
+        } else if hitbox.center().x < block.center().x {
+            player.set_hitbox_x(block.x - hitbox.size.x);
+        } else {
+            player.set_hitbox_x(block.x + block.size.x);
+        }
+    }
+
+    // Blocks harder than the pickaxe tier take less damage, the bigger the difference the less damage
+    fn damage_against(damage: i32, tier: u32, def: &BlockDef) -> i32 {
+        if def.hardness > tier {
+            damage / (1 + def.hardness - tier) as i32
+        } else {
+            damage
+        }
+    }
+
+    // The block under the cursor if it's within reach, otherwise the one next to the player in the facing direction
+    pub fn mining_target(&self, aim: Option<Vec2>) -> Option<Tile> {
+        let player_center = self.player.shape.center();
+
+        if let Some(aim) = aim {
+            let tile = self.world.tile_at(aim);
+            let in_reach = self
+                .world
+                .get(tile)
+                .is_some_and(|block| block.shape.center().distance(player_center) <= MINING_REACH);
+            if in_reach {
+                return Some(tile);
+            }
+        }
+
+        let hitbox = self.player.hitbox();
+        let probe = match self.player.facing {
+            Facing::Left => vec2(hitbox.x - BLOCK_SIZE / 2.0, player_center.y),
+            Facing::Right => vec2(hitbox.x + hitbox.size.x + BLOCK_SIZE / 2.0, player_center.y),
+            Facing::Down => vec2(player_center.x, hitbox.y + hitbox.size.y + BLOCK_SIZE / 2.0),
+        };
+        let tile = self.world.tile_at(probe);
+        self.world.is_solid(tile).then_some(tile)
+    }
+
+    // Tiles of the closest blocks to the player within the auto-miner reach
+    fn auto_miner_targets(&self, count: usize) -> Vec<Tile> {
+        let player_center = self.player.shape.center();
+        let reach = Vec2::splat(AUTO_MINER_REACH);
+        let mut targets: Vec<(Tile, f32)> = self
+            .world
+            .blocks_between(player_center - reach, player_center + reach)
+            .map(|(tile, block)| (tile, block.shape.center().distance(player_center)))
+            .filter(|(_, distance)| *distance <= AUTO_MINER_REACH)
+            .collect();
+        targets.sort_by(|a, b| a.1.total_cmp(&b.1));
+        targets
+            .into_iter()
+            .take(count)
+            .map(|(tile, _)| tile)
+            .collect()
+    }
+
+    // The enemy under the cursor if it's within reach, enemies right next to the player get hit even without aiming
+    fn enemy_target(&self, aim: Option<Vec2>) -> Option<usize> {
+        let player_center = self.player.shape.center();
+
+        if let Some(aim) = aim {
+            let aimed_enemy = self.enemies.iter().position(|enemy| {
+                enemy.shape.contains(aim)
+                    && enemy.shape.center().distance(player_center) <= MINING_REACH
+            });
+            if aimed_enemy.is_some() {
+                return aimed_enemy;
+            }
+        }
+
+        self.enemies
+            .iter()
+            .position(|enemy| enemy.shape.center().distance(player_center) <= BLOCK_SIZE)
+    }
+
+    // Returns true if an enemy was hit
+    fn hit_enemy(&mut self, aim: Option<Vec2>, events: &mut Vec<SimEvent>) -> bool {
+        let Some(index) = self.enemy_target(aim) else {
+            return false;
+        };
+
+        self.damage_enemy(index, self.pickaxe_damage(), events);
+        self.wear_pickaxe(1, events);
+        true
+    }
+
+    // Killed enemies are removed right away, so this can move other enemies around in the vector
+    fn damage_enemy(&mut self, index: usize, damage: i32, events: &mut Vec<SimEvent>) {
+        if self.enemies[index].subtract_hp(damage) {
+            let enemy = self.enemies.swap_remove(index);
+            let def = self.definitions.enemies.get(enemy.enemy_id);
+            enemy.update_score(&mut self.score, def);
+            events.push(SimEvent::GoldEarned(def.gold));
+            events.push(SimEvent::EnemyKilled {
+                enemy_id: enemy.enemy_id,
+                x: enemy.shape.x,
+                y: enemy.shape.y,
+            });
+            events.push(SimEvent::ScoreChanged);
+        }
+    }
+
+    fn mine_target_block(&mut self, aim: Option<Vec2>, events: &mut Vec<SimEvent>) {
+        let Some(tile) = self.mining_target(aim) else {
+            return;
+        };
+        let Some(block_id) = self.world.get(tile).map(|block| block.block_id) else {
+            return;
+        };
+
+        let pickaxe = self.definitions.upgrades.pickaxe(self.effective_pickaxe());
+        let def = self.definitions.blocks.get(block_id);
+        let hp_to_subtract = Self::damage_against(self.pickaxe_damage(), pickaxe.tier, def);
+        let wear = 1 + def.hardness;
+        events.push(SimEvent::BlockClicked(block_id));
+        self.damage_block(tile, hp_to_subtract, events);
+        self.wear_pickaxe(wear, events);
+    }
+
+    fn wear_pickaxe(&mut self, wear: u32, events: &mut Vec<SimEvent>) {
+        if self.player.durability == 0 {
+            return;
+        }
+
+        self.player.durability = self.player.durability.saturating_sub(wear);
+        if self.player.durability == 0 {
+            events.push(SimEvent::PickaxeBroken(self.player.current_pickaxe));
+        }
+    }
+
+    // Destroyed blocks are taken out of the world right away
+    fn damage_block(&mut self, tile: Tile, damage: i32, events: &mut Vec<SimEvent>) {
+        let Some(block) = self.world.get_mut(tile) else {
+            return;
+        };
+        let def = self.definitions.blocks.get(block.block_id);
+
+        // Overkill damage doesn't count
+        events.push(SimEvent::BlockDamaged {
+            block_id: block.block_id,
+            damage: damage.min(block.hp),
+        });
+        let block_destroyed = block.subtract_block_hp(damage);
+        if block_destroyed {
+            let gold_multiplier = self.abilities.gold_multiplier() as f32 * self.bonuses.gold;
+            let gold = self.score.gold;
+            block.update_score(&mut self.score, def, gold_multiplier);
+            events.push(SimEvent::GoldEarned(self.score.gold - gold));
+            events.push(SimEvent::BlockBroken {
+                block_id: block.block_id,
+                x: block.shape.x,
+                y: block.shape.y,
+            });
+            events.push(SimEvent::ScoreChanged);
+            if let Some(item_id) = self.definitions.items.drop_of(block.block_id) {
+                // Ore pays when it's sold, so a gold rush drops more of it instead, as much as fits
+                let count = self.abilities.gold_multiplier().max(1) as u32;
+                let picked = (1..=count).rev().any(|count| {
+                    self.inventory.add(
+                        item_id,
+                        count,
+                        &self.definitions.items,
+                        &self.definitions.upgrades,
+                    )
+                });
+                events.push(if picked {
+                    SimEvent::ItemPicked(item_id)
+                } else {
+                    SimEvent::InventoryFull(item_id)
+                });
+            }
+            self.world.remove(tile);
+        }
+    }
+
+    fn use_ability(&mut self, ability: AbilityId, events: &mut Vec<SimEvent>) {
+        let def = self.definitions.upgrades.ability(ability).clone();
+        let player_center = self.player.shape.center();
+
+        match def.effect {
+            AbilityEffect::Blast { radius, damage } => {
+                let reach = radius * BLOCK_SIZE;
+                let tiles: Vec<Tile> = self
+                    .world
+                    .blocks_between(player_center - reach, player_center + reach)
+                    .filter(|(_, block)| block.shape.center().distance(player_center) <= reach)
+                    .map(|(tile, _)| tile)
+                    .collect();
+                for tile in tiles {
+                    self.damage_block(tile, damage, events);
+                }
+
+                // Backwards because killed enemies get swapped out of the vector
+                for index in (0..self.enemies.len()).rev() {
+                    if self.enemies[index].shape.center().distance(player_center) <= reach {
+                        self.damage_enemy(index, damage, events);
+                    }
+                }
+            }
+            AbilityEffect::GroundPound => {
+                let hitbox = self.player.hitbox();
+                let feet = vec2(player_center.x, hitbox.y + hitbox.size.y + BLOCK_SIZE / 2.0);
+                let (_, row) = self.world.tile_at(feet);
+                for column in 0..self.params.needed_x {
+                    if let Some(hp) = self.world.get((column, row)).map(|block| block.hp) {
+                        self.damage_block((column, row), hp, events);
+                    }
+                }
+            }
+            AbilityEffect::GoldRush {
+                duration,
+                multiplier,
+            } => {
+                self.abilities.gold_rush = Some(GoldRush {
+                    remaining: duration,
+                    multiplier,
+                });
+            }
+        }
+
+        self.abilities.set_cooldown(ability, def.cooldown);
+        events.push(SimEvent::AbilityUsed(ability));
+    }
+
+    fn pickaxe_damage(&self) -> i32 {
+        let pickaxe = self.definitions.upgrades.pickaxe(self.effective_pickaxe());
+        let mut damage = pickaxe.damage as f32 * self.bonuses.damage;
+        if self.durability_fraction() < WORN_DURABILITY {
+            damage *= WORN_DAMAGE_MULTIPLIER;
+        }
+        damage.round() as i32
+    }
+
+    // A broken pickaxe is only as good as the one every run starts with until it's repaired
+    pub fn effective_pickaxe(&self) -> PickaxeId {
+        if self.player.durability == 0 {
+            self.definitions.upgrades.starting_pickaxe()
+        } else {
+            self.player.current_pickaxe
+        }
+    }
+
+    pub fn max_durability(&self) -> u32 {
+        self.definitions
+            .upgrades
+            .pickaxe(self.player.current_pickaxe)
+            .durability
+    }
+
+    pub fn durability_fraction(&self) -> f32 {
+        self.player.durability as f32 / self.max_durability() as f32
+    }
+
+    // Gold to bring the pickaxe back to full durability
+    pub fn repair_price(&self) -> i32 {
+        let missing = self.max_durability() - self.player.durability;
+        (missing as f32 * self.definitions.upgrades.repair.gold_per_point).ceil() as i32
+    }
+
+    // Why a recipe can't be queued right now, or None if it can
+    pub fn craft_blocker(&self, recipe: RecipeId) -> Option<String> {
+        if self.crafting.is_full() {
+            return Some(String::from("Queue full"));
+        }
+
+        let recipe_def = self.definitions.recipes.get(recipe);
+        let upgrades = &self.definitions.upgrades;
+        let upgrade_blocker = match recipe_def.output {
+            RecipeOutput::Item { .. } => None,
+            _ if self.crafting.is_queued(recipe) => Some(String::from("Already queued")),
+            RecipeOutput::Pickaxe(pickaxe) => {
+                upgrades.pickaxe_unlock_blocker(self.player.current_pickaxe, pickaxe)
+            }
+            RecipeOutput::StorageUpgrade(upgrade) => {
+                upgrades.storage_unlock_blocker(self.inventory.owned_storage(), upgrade)
+            }
+        };
+        if upgrade_blocker.is_some() {
+            return upgrade_blocker;
+        }
+
+        let missing = recipe_def
+            .ingredients
+            .iter()
+            .find(|&&(item, count)| self.inventory.count(item) < count);
+        if let Some(&(item, count)) = missing {
+            let item = self.definitions.items.get(item);
+            return Some(format!("Needs {} {}", count, item.name));
+        }
+
+        None
+    }
+
+    // Gold the backpack would sell for right now, prestige bonuses included
+    pub fn sell_value(&self) -> i32 {
+        (self.inventory.value(&self.definitions.items) as f32 * self.bonuses.gold).round() as i32
+    }
+
+    // Runes the player would get for resetting the world now, None if the run didn't get far enough yet
+    pub fn prestige_reward(&self) -> Option<u32> {
+        let prestige = self.definitions.upgrades.prestige.as_ref()?;
+        if self.deepest_row < prestige.min_row && self.score.current_score < prestige.min_score {
+            return None;
+        }
+        Some(prestige.runes_for(self.score.current_score))
+    }
+
+    // Fingerprint of everything that matters for the outcome of the run, replays compare it to check
+    // they ended up in the same place
+    pub fn state_hash(&self) -> u64 {
+        let mut hasher = Fnv1a::new();
+        hasher.write_i32(self.score.current_score);
+        hasher.write_i32(self.score.blocks_destroyed);
+        hasher.write_i32(self.score.gold);
+        hasher.write_u32(self.player.shape.x.to_bits());
+        hasher.write_u32(self.player.offset_y.to_bits());
+        hasher.write_u32(self.player.speed.to_bits());
+        hasher.write_i32(self.player.health);
+        hasher.write_usize(self.player.current_pickaxe.0);
+        hasher.write_u32(self.player.durability);
+        hasher.write_i64(self.deepest_row);
+        hasher.write_u32(self.ladders);
+        for (item_id, _) in self.definitions.items.iter() {
+            hasher.write_u32(self.inventory.count(item_id));
+        }
+        for (upgrade_id, _) in self.definitions.upgrades.storage_upgrades() {
+            hasher.write_u8(self.inventory.owns_storage(upgrade_id) as u8);
+        }
+        for job in self.crafting.jobs() {
+            hasher.write_usize(job.recipe.0);
+            hasher.write_u32(job.elapsed.to_bits());
+        }
+        for (column, row) in self.world.ladders() {
+            hasher.write_i32(column);
+            hasher.write_i64(row);
+        }
+        for (_, block) in self.world.blocks() {
+            hasher.write_u32(block.shape.x.to_bits());
+            hasher.write_u32(block.shape.y.to_bits());
+            hasher.write_usize(block.block_id.0);
+            hasher.write_i32(block.hp);
+        }
+        for enemy in &self.enemies {
+            hasher.write_u32(enemy.shape.x.to_bits());
+            hasher.write_u32(enemy.shape.y.to_bits());
+            hasher.write_usize(enemy.enemy_id.0);
+            hasher.write_i32(enemy.hp);
+        }
+        hasher.finish()
+    }
+
+    // Only rows generated from now on are affected
+    pub fn set_strata_multiplier(&mut self, multiplier: f32) {
+        self.terrain.strata_multiplier = multiplier;
+    }
+
+    // The shop is only open while standing at the surface
+    pub fn is_at_base(&self) -> bool {
+        self.row_of(self.player.shape.y + self.player.shape.size.y) <= BASE_ROW
+    }
+
+    // World y where the surface base stands
+    pub fn surface_y(&self) -> f32 {
+        self.row_y(BASE_ROW)
+    }
+
+    fn is_on_ladder(&self) -> bool {
+        // One pixel under the feet too, so the player can stand on top of a ladder
+        let hitbox = self.player.hitbox();
+        self.world
+            .tiles_between(
+                vec2(hitbox.x, hitbox.y),
+                vec2(hitbox.x + hitbox.size.x, hitbox.y + hitbox.size.y + 1.0),
+            )
+            .any(|tile| self.world.has_ladder(tile))
+    }
+
+    // Whether the player is walking into a block on the side
+    fn is_against_wall(&self, move_x: f32) -> bool {
+        let hitbox = self.player.hitbox();
+        let x = if move_x < 0.0 {
+            hitbox.x - 1.0
+        } else if move_x > 0.0 {
+            hitbox.x + hitbox.size.x + 1.0
+        } else {
+            return false;
+        };
+        self.world
+            .is_solid(self.world.tile_at(vec2(x, hitbox.center().y)))
+    }
+
+    pub fn is_game_over(&self) -> bool {
+        self.player.is_dead() || self.retired
+    }
+
+    pub fn world_width(&self) -> f32 {
+        self.params.needed_x as f32 * BLOCK_SIZE
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const VIEWPORT: Vec2 = vec2(1056.0, 800.0);
+    const TICK: f32 = 1.0 / 60.0;
+
+    fn simulation() -> Simulation {
+        let mut simulation = Simulation::new(VIEWPORT, 42, Rc::new(Definitions::shipped()));
+        simulation.enemies_enabled = false;
+        simulation
+    }
+
+    // Runs until the player stands on something, or panics after a few seconds
+    fn land(simulation: &mut Simulation) {
+        for _ in 0..300 {
+            simulation.update(&SimInput::default(), TICK, VIEWPORT);
+            if simulation.player.grounded {
+                return;
+            }
+        }
+        panic!("the player never landed");
+    }
+
+    // Puts a block right under the player's feet and returns its tile
+    fn block_under_player(simulation: &mut Simulation, id: &str) -> Tile {
+        let hitbox = simulation.player.hitbox();
+        let feet = vec2(
+            hitbox.x + hitbox.size.x / 2.0,
+            hitbox.y + hitbox.size.y + BLOCK_SIZE / 2.0,
+        );
+        let tile = simulation.world.tile_at(feet);
+        let block_id = simulation.definitions.blocks.find(id).unwrap();
+        let def = simulation.definitions.blocks.get(block_id).clone();
+        simulation.world.place(tile, block_id, &def);
+        tile
+    }
+
+    fn mine_until_broken(simulation: &mut Simulation, tile: Tile) {
+        let input = SimInput {
+            aim: Some(simulation.world.get(tile).unwrap().shape.center()),
+            mine_pressed: true,
+            ..Default::default()
+        };
+        for _ in 0..300 {
+            simulation.update(&input, TICK, VIEWPORT);
+            if !simulation.world.is_solid(tile) {
+                return;
+            }
+        }
+        panic!("the block never broke");
+    }
+
+    // Sell value of a single ore block, with or without a gold rush going on
+    fn ore_value(gold_rush: bool) -> i32 {
+        let mut simulation = simulation();
+        land(&mut simulation);
+        if gold_rush {
+            simulation.abilities.gold_rush = Some(GoldRush {
+                remaining: 20.0,
+                multiplier: 2,
+            });
+        }
+        let tile = block_under_player(&mut simulation, "gold");
+        mine_until_broken(&mut simulation, tile);
+        simulation.sell_value()
+    }
+
+    #[test]
+    fn the_player_falls_onto_the_first_row() {
+        let mut simulation = simulation();
+        assert!(!simulation.player.grounded);
+
+        land(&mut simulation);
+        // Row 1 is the first row of blocks, its top is a block below the surface line
+        let hitbox = simulation.player.hitbox();
+        assert_eq!(simulation.player.speed, 0.0);
+        assert_eq!(
+            hitbox.y + hitbox.size.y,
+            simulation.params.block_area_top + BLOCK_SIZE
+        );
+    }
+
+    // A dwarf that was standing still gets hit by a cave rat
+    fn touch_enemy(simulation: &mut Simulation) -> Vec<SimEvent> {
+        let enemy_id = simulation.definitions.enemies.find("cave_rat").unwrap();
+        let def = simulation.definitions.enemies.get(enemy_id).clone();
+        let shape = Simulation::enemy_shape(simulation.player.hitbox().center(), def.color);
+        simulation.enemies.push(Enemy::new(shape, enemy_id, &def));
+        simulation.update(&SimInput::default(), TICK, VIEWPORT)
+    }
+
+    #[test]
+    fn long_frames_dont_fall_through_a_row() {
+        // As long as the longest frame the game lets through
+        let max_frame_time = 0.25;
+        let mut simulation = simulation();
+        simulation.player.offset_y = BLOCK_SIZE * 30.0;
+        for _ in 0..20 {
+            simulation.update(&SimInput::default(), max_frame_time, VIEWPORT);
+        }
+
+        let hitbox = simulation.player.hitbox();
+        assert!(simulation.player.grounded);
+        assert_eq!(
+            hitbox.y + hitbox.size.y,
+            simulation.params.block_area_top + BLOCK_SIZE
+        );
+    }
+
+    #[test]
+    fn walls_stop_the_player() {
+        let mut simulation = simulation();
+        land(&mut simulation);
+        let hitbox = simulation.player.hitbox();
+        let tile = simulation.world.tile_at(vec2(
+            hitbox.x + hitbox.size.x + BLOCK_SIZE * 2.0,
+            hitbox.center().y,
+        ));
+        let dirt = simulation.definitions.blocks.find("dirt").unwrap();
+        let def = simulation.definitions.blocks.get(dirt).clone();
+        let wall_x = simulation.world.place(tile, dirt, &def).unwrap().shape.x;
+
+        let input = SimInput {
+            move_x: 1.0,
+            ..Default::default()
+        };
+        for _ in 0..60 {
+            simulation.update(&input, TICK, VIEWPORT);
+        }
+        let hitbox = simulation.player.hitbox();
+        assert_eq!(hitbox.x + hitbox.size.x, wall_x);
+        assert!(simulation.player.grounded);
+        assert!(simulation.world.is_solid(tile));
+    }
+
+    #[test]
+    fn enemies_hurt_the_player_on_contact() {
+        let mut simulation = simulation();
+        land(&mut simulation);
+        let events = touch_enemy(&mut simulation);
+
+        let enemy_id = simulation.definitions.enemies.find("cave_rat").unwrap();
+        let damage = simulation.definitions.enemies.get(enemy_id).damage;
+        assert!(events
+            .iter()
+            .any(|event| matches!(event, SimEvent::PlayerHurt)));
+        assert_eq!(simulation.player.health, MAX_HEALTH - damage);
+
+        // Still recovering from the first hit
+        simulation.update(&SimInput::default(), TICK, VIEWPORT);
+        assert_eq!(simulation.player.health, MAX_HEALTH - damage);
+    }
+
+    #[test]
+    fn the_run_is_over_once_the_player_dies() {
+        let mut simulation = simulation();
+        land(&mut simulation);
+        simulation.player.health = 1;
+        let events = touch_enemy(&mut simulation);
+        assert!(events
+            .iter()
+            .any(|event| matches!(event, SimEvent::PlayerDied)));
+        assert!(simulation.is_game_over());
+
+        // Nothing moves anymore
+        let run_time = simulation.run_time;
+        let input = SimInput {
+            move_x: 1.0,
+            mine_pressed: true,
+            ..Default::default()
+        };
+        assert!(simulation.update(&input, TICK, VIEWPORT).is_empty());
+        assert_eq!(simulation.run_time, run_time);
+    }
+
+    #[test]
+    fn broken_saved_chunks_are_invalid() {
+        let mut simulation = simulation();
+        let width = simulation.params.needed_x;
+        let chunk = |tiles: usize, damaged: Vec<(u32, i32)>| SavedChunk {
+            chunk: -1,
+            width,
+            blocks: vec![String::from("dirt")],
+            tiles: vec![1; tiles],
+            damaged,
+        };
+        let tile_count = width as usize * CHUNK_ROWS as usize;
+
+        assert!(simulation
+            .load_chunk(&chunk(tile_count, vec![(0, 10)]))
+            .is_ok());
+        assert!(matches!(
+            simulation.load_chunk(&chunk(tile_count - 1, Vec::new())),
+            Err(SaveError::Invalid(_))
+        ));
+        assert!(matches!(
+            simulation.load_chunk(&chunk(tile_count, vec![(tile_count as u32, 10)])),
+            Err(SaveError::Invalid(_))
+        ));
+    }
+
+    #[test]
+    fn offline_gold_counts_as_earned() {
+        let mut simulation = simulation();
+        let (auto_miner, _) = simulation
+            .definitions
+            .upgrades
+            .auto_miners()
+            .next()
+            .unwrap();
+        simulation.auto_miners.set_count(auto_miner, 1);
+
+        let mut events = Vec::new();
+        let report = simulation
+            .apply_offline_progress(3600.0, &mut events)
+            .unwrap();
+        assert!(report.gold > 0);
+        assert_eq!(simulation.score.gold, report.gold);
+        assert!(events
+            .iter()
+            .any(|event| matches!(event, SimEvent::GoldEarned(gold) if *gold == report.gold)));
+    }
+
+    #[test]
+    fn saves_keep_where_the_player_walked_to() {
+        let mut simulation = simulation();
+        land(&mut simulation);
+        let input = SimInput {
+            move_x: 1.0,
+            ..Default::default()
+        };
+        for _ in 0..30 {
+            simulation.update(&input, TICK, VIEWPORT);
+        }
+        assert_ne!(simulation.player.shape.x, (VIEWPORT.x - PLAYER_SIZE) / 2.0);
+
+        let loaded = Simulation::from_save(
+            &simulation.to_save(),
+            VIEWPORT,
+            simulation.definitions.clone(),
+        )
+        .unwrap();
+        assert_eq!(loaded.player.shape.x, simulation.player.shape.x);
+        assert_eq!(loaded.player.previous_position.x, simulation.player.shape.x);
+    }
+
+    #[test]
+    fn the_shop_ignores_commands_when_the_mode_has_none() {
+        let mut simulation = simulation();
+        land(&mut simulation);
+        assert!(simulation.is_at_base());
+        simulation.score.gold = 10_000;
+        let input = SimInput {
+            commands: vec![Command::BuyLadders],
+            ..Default::default()
+        };
+
+        simulation.shop_enabled = false;
+        simulation.update(&input, TICK, VIEWPORT);
+        assert_eq!(simulation.ladders, 0);
+        assert_eq!(simulation.score.gold, 10_000);
+
+        simulation.shop_enabled = true;
+        simulation.update(&input, TICK, VIEWPORT);
+        assert!(simulation.ladders > 0);
+    }
+
+    #[test]
+    fn blocks_break_after_enough_hits() {
+        let mut simulation = simulation();
+        land(&mut simulation);
+        let tile = block_under_player(&mut simulation, "dirt");
+        let block = simulation.world.get(tile).unwrap();
+        let (block_id, x, y, aim) = (
+            block.block_id,
+            block.shape.x,
+            block.shape.y,
+            block.shape.center(),
+        );
+        let def = simulation.definitions.blocks.get(block_id).clone();
+        let pickaxe = simulation
+            .definitions
+            .upgrades
+            .pickaxe(simulation.effective_pickaxe());
+        let damage = Simulation::damage_against(simulation.pickaxe_damage(), pickaxe.tier, &def);
+        let hits = (def.base_hp + damage - 1) / damage;
+        assert!(hits > 1);
+
+        let input = SimInput {
+            aim: Some(aim),
+            mine_pressed: true,
+            ..Default::default()
+        };
+        for _ in 1..hits {
+            let events = simulation.update(&input, TICK, VIEWPORT);
+            assert!(!events
+                .iter()
+                .any(|event| matches!(event, SimEvent::BlockBroken { .. })));
+            assert!(simulation.world.is_solid(tile));
+        }
+        assert_eq!(simulation.score.blocks_destroyed, 0);
+
+        let events = simulation.update(&input, TICK, VIEWPORT);
+        assert!(!simulation.world.is_solid(tile));
+        assert!(events.iter().any(|event| matches!(
+            event,
+            SimEvent::BlockBroken { block_id: broken, x: broken_x, y: broken_y }
+                if *broken == block_id && *broken_x == x && *broken_y == y
+        )));
+        assert_eq!(simulation.score.blocks_destroyed, 1);
+        assert_eq!(simulation.score.current_score, def.score);
+        assert_eq!(simulation.score.gold, def.gold);
+    }
+
+    #[test]
+    fn gold_rush_multiplies_the_ore_picked_up() {
+        let value = ore_value(false);
+        assert!(value > 0);
+        assert_eq!(ore_value(true), value * 2);
+    }
+}
@@ -0,0 +1,137 @@
+use std::collections::{BTreeMap, BTreeSet};
+
+use serde::{Deserialize, Serialize};
+
+use crate::save::{self, SaveError};
+
+const BLOCKS_CSV_FILE: &str = "dwarfing_stats_blocks.csv";
+const RUNS_CSV_FILE: &str = "dwarfing_stats_runs.csv";
+const JSON_FILE: &str = "dwarfing_stats.json";
+const MAX_RUNS: usize = 100; // Only the latest runs are kept
+
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+pub struct BlockStats {
+    pub destroyed: u64,
+    pub clicks: u64,
+    pub damage: u64,
+}
+
+// A finished run, either by dying or by prestiging
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct RunRecord {
+    pub started_at: u64, // Unix time in seconds
+    pub duration: f32,   // Seconds
+    pub score: i32,
+    pub gold: i32,
+    pub blocks_destroyed: i32,
+    pub deepest_row: i64,
+}
+
+// Lifetime statistics kept in the profile, the achievements are checked against them too
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+pub struct Statistics {
+    pub blocks: BTreeMap<String, BlockStats>, // By block id
+    pub enemies_killed: u64,
+    pub gold_earned: u64,
+    pub gold_spent: u64,
+    pub deepest_row: i64,
+    pub seconds_played: f32,
+    pub pickaxes_bought: BTreeSet<String>,
+    pub runs: Vec<RunRecord>, // Oldest first
+}
+
+impl Statistics {
+    pub fn block(&mut self, id: &str) -> &mut BlockStats {
+        self.blocks.entry(id.to_string()).or_default()
+    }
+
+    pub fn blocks_destroyed(&self) -> u64 {
+        self.blocks.values().map(|block| block.destroyed).sum()
+    }
+
+    pub fn add_run(&mut self, run: RunRecord) {
+        self.runs.push(run);
+        if self.runs.len() > MAX_RUNS {
+            self.runs.remove(0);
+        }
+    }
+
+    // Writes a CSV file for the blocks and another one for the runs, returns the file names
+    pub fn export_csv(&self) -> Result<String, SaveError> {
+        let mut blocks = String::from("block,destroyed,clicks,damage\n");
+        for (id, block) in &self.blocks {
+            blocks += &format!(
+                "{},{},{},{}\n",
+                id, block.destroyed, block.clicks, block.damage
+            );
+        }
+        save::write_atomic(BLOCKS_CSV_FILE, &blocks)?;
+
+        let mut runs =
+            String::from("date,duration_seconds,score,gold,blocks_destroyed,deepest_row\n");
+        for run in &self.runs {
+            runs += &format!(
+                "{},{:.0},{},{},{},{}\n",
+                format_date(run.started_at),
+                run.duration,
+                run.score,
+                run.gold,
+                run.blocks_destroyed,
+                run.deepest_row
+            );
+        }
+        save::write_atomic(RUNS_CSV_FILE, &runs)?;
+
+        Ok(format!("{} and {}", BLOCKS_CSV_FILE, RUNS_CSV_FILE))
+    }
+
+    // Returns the file name
+    pub fn export_json(&self) -> Result<String, SaveError> {
+        save::write_atomic(JSON_FILE, &serde_json::to_string_pretty(self)?)?;
+        Ok(String::from(JSON_FILE))
+    }
+}
+
+// Days since 1970-01-01 to a (year, month, day) date, see http://howardhinnant.github.io/date_algorithms.html
+pub fn civil_from_days(days: i64) -> (i64, u32, u32) {
+    let z = days + 719_468;
+    let era = z.div_euclid(146_097);
+    let day_of_era = z.rem_euclid(146_097);
+    let year_of_era =
+        (day_of_era - day_of_era / 1460 + day_of_era / 36_524 - day_of_era / 146_096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let shifted_month = (5 * day_of_year + 2) / 153;
+    let day = (day_of_year - (153 * shifted_month + 2) / 5 + 1) as u32;
+    let month = if shifted_month < 10 {
+        shifted_month + 3
+    } else {
+        shifted_month - 9
+    } as u32;
+    let year = year_of_era + era * 400 + if month <= 2 { 1 } else { 0 };
+    (year, month, day)
+}
+
+// UTC date and time in the "YYYY-MM-DD HH:MM" format
+pub fn format_date(unix_time: u64) -> String {
+    let (year, month, day) = civil_from_days((unix_time / 86_400) as i64);
+    let seconds_of_day = unix_time % 86_400;
+    format!(
+        "{:04}-{:02}-{:02} {:02}:{:02}",
+        year,
+        month,
+        day,
+        seconds_of_day / 3600,
+        seconds_of_day % 3600 / 60
+    )
+}
+
+// "1h 02m 03s" style durations
+pub fn format_duration(seconds: f32) -> String {
+    let seconds = seconds.max(0.0) as u64;
+    format!(
+        "{}h {:02}m {:02}s",
+        seconds / 3600,
+        seconds % 3600 / 60,
+        seconds % 60
+    )
+}
@@ -0,0 +1,170 @@
+use crate::{
+    block_registry::BlockId,
+    definitions::Definitions,
+    noise::uniform_noise,
+    rng::{hash_point, Rng},
+};
+
+// Caves
+const CAVE_MIN_ROW: i64 = 8; // Keep the surface solid
+const CAVE_SCALE: f32 = 0.14;
+const CAVE_CHANCE_PER_ROW: f32 = 0.003;
+const MAX_CAVE_CHANCE: f32 = 0.16;
+
+// Hard strata bands
+const STRATA_MIN_ROW: i64 = 25;
+const STRATA_SCALE: f32 = 0.09;
+const STRATA_WOBBLE: f32 = 0.04; // How much the bands bend from side to side
+const STRATA_CHANCE: f32 = 0.2;
+
+// Small per-block randomness so the edges of the veins aren't perfectly smooth
+const VEIN_JITTER: f32 = 0.1;
+
+// Every noise field gets its own seed so caves, strata and veins don't line up
+const CAVE_SALT: i64 = 1;
+const STRATA_SALT: i64 = 2;
+const VEIN_SALT: i64 = 3;
+
+// Coherent noise based world generation, still done one row at a time so the world can be infinite
+pub struct Terrain {
+    seed: u64,
+    pub strata_multiplier: f32, // Changed by the daily challenges
+}
+
+impl Terrain {
+    pub fn new(seed: u64) -> Self {
+        Self {
+            seed,
+            strata_multiplier: 1.0,
+        }
+    }
+
+    // Block for a single cell, None means the cell is part of a cave.
+    // `y` is the world y of the row, which is what the spawn curves expect.
+    pub fn block_at(
+        &self,
+        definitions: &Definitions,
+        column: i32,
+        row: i64,
+        y: f32,
+        rng: &mut Rng,
+    ) -> Option<BlockId> {
+        let biome = definitions.biomes.get(definitions.biomes.biome_at(row));
+        if self.is_cave(column, row, biome.cave_multiplier) {
+            return None;
+        }
+
+        let in_strata = self.is_strata(column, row) && biome.strata.is_some();
+
+        // Blocks with a spawn curve are checked in the order they appear in the file
+        for (id, block) in definitions.blocks.iter() {
+            let Some(spawn) = &block.spawn else {
+                continue;
+            };
+            if !biome.allows_block(id) || (in_strata && !block.ore) {
+                continue;
+            }
+
+            let probability = spawn.probability(y);
+            if probability <= 0.0 {
+                continue;
+            }
+
+            let vein = uniform_noise(
+                hash_point(self.seed, VEIN_SALT, id.0 as i64),
+                column as f32 * spawn.vein_scale,
+                row as f32 * spawn.vein_scale,
+            );
+            let jitter = (rng.next_f32() - 0.5) * VEIN_JITTER;
+            if vein + jitter > 1.0 - probability {
+                return Some(id);
+            }
+        }
+
+        if in_strata {
+            return biome.strata;
+        }
+        Some(biome.fill)
+    }
+
+    fn is_cave(&self, column: i32, row: i64, cave_multiplier: f32) -> bool {
+        if row < CAVE_MIN_ROW {
+            return false;
+        }
+
+        // Caves get more common the deeper you go, and some biomes are more hollow than others
+        let cave_chance = ((row - CAVE_MIN_ROW) as f32 * CAVE_CHANCE_PER_ROW).min(MAX_CAVE_CHANCE)
+            * cave_multiplier;
+        let cave = uniform_noise(
+            hash_point(self.seed, CAVE_SALT, 0),
+            column as f32 * CAVE_SCALE,
+            row as f32 * CAVE_SCALE,
+        );
+        cave > 1.0 - cave_chance
+    }
+
+    fn is_strata(&self, column: i32, row: i64) -> bool {
+        if row < STRATA_MIN_ROW {
+            return false;
+        }
+
+        let band = uniform_noise(
+            hash_point(self.seed, STRATA_SALT, 0),
+            column as f32 * STRATA_WOBBLE,
+            row as f32 * STRATA_SCALE,
+        );
+        band > 1.0 - STRATA_CHANCE * self.strata_multiplier
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::simulation::BLOCK_SIZE;
+
+    const WIDTH: i32 = 33;
+    const ROWS: i64 = 200; // Deep enough for caves, strata and the first ores
+
+    // Every row the way the simulation spawns them, one rng per row
+    fn generate(seed: u64, rows: impl Iterator<Item = i64>) -> Vec<(i64, Vec<Option<BlockId>>)> {
+        let definitions = Definitions::shipped();
+        let terrain = Terrain::new(seed);
+        let mut generated: Vec<(i64, Vec<Option<BlockId>>)> = rows
+            .map(|row| {
+                let mut rng = Rng::for_row(seed, row);
+                let tiles = (0..WIDTH)
+                    .map(|column| {
+                        terrain.block_at(
+                            &definitions,
+                            column,
+                            row,
+                            row as f32 * BLOCK_SIZE,
+                            &mut rng,
+                        )
+                    })
+                    .collect();
+                (row, tiles)
+            })
+            .collect();
+        generated.sort_by_key(|(row, _)| *row);
+        generated
+    }
+
+    #[test]
+    fn the_same_seed_gives_the_same_layout_in_any_order() {
+        let in_order = generate(1234, 1..=ROWS);
+        let backwards = generate(1234, (1..=ROWS).rev());
+        // Every other row first, like chunks coming back after being evicted
+        let interleaved = generate(1234, (1..=ROWS).step_by(2).chain((2..=ROWS).step_by(2)));
+        assert_eq!(in_order, backwards);
+        assert_eq!(in_order, interleaved);
+
+        // Not just a wall of fill blocks
+        assert!(in_order.iter().any(|(_, tiles)| tiles.contains(&None)));
+    }
+
+    #[test]
+    fn different_seeds_give_different_layouts() {
+        assert_ne!(generate(1234, 1..=ROWS), generate(5678, 1..=ROWS));
+    }
+}
@@ -0,0 +1,523 @@
+use serde::{Deserialize, Serialize};
+
+// Index of a pickaxe definition inside the catalog
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub struct PickaxeId(pub usize);
+
+#[derive(Clone, Debug, Deserialize)]
+pub struct PickaxeDef {
+    pub id: String,
+    pub name: String,
+    pub price: i32,
+    pub damage: i32,
+    // Player sprite used while this pickaxe is equipped
+    pub texture: String,
+    // Blocks with a higher hardness than this take reduced damage
+    pub tier: u32,
+    // Pickaxe that has to be owned (or surpassed) before this one can be bought
+    pub requires: Option<String>,
+    // Wear it can take before breaking, every hit wears it by 1 + the hardness of the block
+    #[serde(default = "default_durability")]
+    pub durability: u32,
+}
+
+fn default_durability() -> u32 {
+    200
+}
+
+// Index of an ability definition inside the catalog, also decides its hotkey (1, 2, 3...)
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub struct AbilityId(pub usize);
+
+#[derive(Clone, Debug, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum AbilityEffect {
+    // Damages every block and enemy within `radius` blocks of the player
+    Blast { radius: f32, damage: i32 },
+    // Breaks the whole row under the player
+    GroundPound,
+    // Multiplies the gold from broken blocks for a while
+    GoldRush { duration: f32, multiplier: i32 },
+}
+
+#[derive(Clone, Debug, Deserialize)]
+pub struct AbilityDef {
+    pub id: String,
+    pub name: String,
+    pub price: i32,
+    pub cooldown: f32, // Seconds
+    pub effect: AbilityEffect,
+}
+
+// Index of an auto-miner definition inside the catalog
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub struct AutoMinerId(pub usize);
+
+// Helpers that chip at the blocks around the player on their own, they can be bought many times
+#[derive(Clone, Debug, Deserialize)]
+pub struct AutoMinerDef {
+    pub id: String,
+    pub name: String,
+    pub base_price: i32,
+    // Every helper already owned makes the next one this much more expensive
+    pub price_growth: f32,
+    pub damage: i32,
+    pub interval: f32, // Seconds between hits
+}
+
+impl AutoMinerDef {
+    pub fn price(&self, owned: u32) -> i32 {
+        (self.base_price as f32 * self.price_growth.powi(owned as i32)).round() as i32
+    }
+}
+
+// How much the auto-miners earn while the game is closed
+#[derive(Clone, Debug, Deserialize)]
+pub struct OfflineConfig {
+    pub max_hours: f32,
+    // Fraction of what the auto-miners would have earned while playing
+    pub efficiency: f32,
+}
+
+impl Default for OfflineConfig {
+    fn default() -> Self {
+        Self {
+            max_hours: 8.0,
+            efficiency: 0.5,
+        }
+    }
+}
+
+// Ladders are bought in bundles and placed one per tile to climb back up to the base
+#[derive(Clone, Debug, Deserialize)]
+pub struct LadderConfig {
+    pub price: i32, // For the whole bundle
+    pub bundle: u32,
+}
+
+impl Default for LadderConfig {
+    fn default() -> Self {
+        Self {
+            price: 10,
+            bundle: 5,
+        }
+    }
+}
+
+// What the backpack holds before any storage upgrade
+#[derive(Clone, Debug, Deserialize)]
+pub struct BackpackConfig {
+    pub slots: u32,
+    pub max_weight: f32, // In kg
+}
+
+impl Default for BackpackConfig {
+    fn default() -> Self {
+        Self {
+            slots: 3,
+            max_weight: 30.0,
+        }
+    }
+}
+
+// Index of a storage upgrade definition inside the catalog
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub struct StorageUpgradeId(pub usize);
+
+#[derive(Clone, Debug, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum StorageEffect {
+    // Adds to the backpack limits
+    Capacity { slots: u32, weight: f32 },
+    // Multiplies the weight of everything in the backpack
+    Compression { weight_multiplier: f32 },
+}
+
+// Bought once each, they make more ore fit before having to go back up
+#[derive(Clone, Debug, Deserialize)]
+pub struct StorageUpgradeDef {
+    pub id: String,
+    pub name: String,
+    pub price: i32,
+    pub effect: StorageEffect,
+    // Storage upgrade that has to be owned before this one can be bought
+    #[serde(default)]
+    pub requires: Option<String>,
+}
+
+// Fixing the pickaxe in the shop, the price goes by how worn it is
+#[derive(Clone, Debug, Deserialize)]
+pub struct RepairConfig {
+    pub gold_per_point: f32,
+}
+
+impl Default for RepairConfig {
+    fn default() -> Self {
+        Self {
+            gold_per_point: 0.2,
+        }
+    }
+}
+
+// Index of a permanent upgrade inside the prestige config
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct PrestigeUpgradeId(pub usize);
+
+#[derive(Clone, Copy, Debug, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum PrestigeEffect {
+    Damage,
+    Gold,
+    FallSpeed,
+}
+
+// Upgrades bought with runes, they are kept between runs
+#[derive(Clone, Debug, Deserialize)]
+pub struct PrestigeUpgradeDef {
+    pub id: String,
+    pub name: String,
+    pub effect: PrestigeEffect,
+    // Added to the multiplier of the effect for every level
+    pub per_level: f32,
+    pub base_price: u32,
+    pub price_growth: f32,
+    pub max_level: u32,
+}
+
+impl PrestigeUpgradeDef {
+    pub fn price(&self, level: u32) -> u32 {
+        (self.base_price as f32 * self.price_growth.powi(level as i32)).round() as u32
+    }
+}
+
+// When a run can be traded for runes and how many it gives
+#[derive(Clone, Debug, Default, Deserialize)]
+pub struct PrestigeConfig {
+    // Reaching either of these is enough to prestige
+    pub min_row: i64,
+    pub min_score: i32,
+    // runes = floor(sqrt(score / score_per_rune))
+    pub score_per_rune: f32,
+    upgrades: Vec<PrestigeUpgradeDef>,
+}
+
+impl PrestigeConfig {
+    pub fn runes_for(&self, score: i32) -> u32 {
+        (score.max(0) as f32 / self.score_per_rune).sqrt().floor() as u32
+    }
+
+    pub fn upgrade(&self, id: PrestigeUpgradeId) -> &PrestigeUpgradeDef {
+        &self.upgrades[id.0]
+    }
+
+    pub fn upgrades(&self) -> impl Iterator<Item = (PrestigeUpgradeId, &PrestigeUpgradeDef)> {
+        self.upgrades
+            .iter()
+            .enumerate()
+            .map(|(index, upgrade)| (PrestigeUpgradeId(index), upgrade))
+    }
+}
+
+// Everything that can be bought in the shop, loaded from upgrades.json
+#[derive(Debug, Deserialize)]
+pub struct UpgradeCatalog {
+    pickaxes: Vec<PickaxeDef>,
+    #[serde(default)]
+    abilities: Vec<AbilityDef>,
+    #[serde(default)]
+    auto_miners: Vec<AutoMinerDef>,
+    #[serde(default)]
+    pub ladders: LadderConfig,
+    #[serde(default)]
+    pub repair: RepairConfig,
+    #[serde(default)]
+    pub backpack: BackpackConfig,
+    #[serde(default)]
+    storage: Vec<StorageUpgradeDef>,
+    #[serde(default)]
+    pub offline: OfflineConfig,
+    // Without a prestige section the runs can't be prestiged
+    pub prestige: Option<PrestigeConfig>,
+}
+
+impl UpgradeCatalog {
+    pub fn from_json(json: &str) -> Result<Self, String> {
+        let catalog: UpgradeCatalog = serde_json::from_str(json).map_err(|err| err.to_string())?;
+
+        if catalog.pickaxes.is_empty() {
+            return Err(String::from("at least one pickaxe is needed"));
+        }
+
+        for (index, pickaxe) in catalog.pickaxes.iter().enumerate() {
+            if catalog.pickaxes[..index]
+                .iter()
+                .any(|other| other.id == pickaxe.id)
+            {
+                return Err(format!("duplicated pickaxe id '{}'", pickaxe.id));
+            }
+            if pickaxe.durability == 0 {
+                return Err(format!(
+                    "pickaxe '{}' needs a positive durability",
+                    pickaxe.id
+                ));
+            }
+            if let Some(requires) = &pickaxe.requires {
+                if catalog.find_pickaxe(requires).is_none() {
+                    return Err(format!(
+                        "pickaxe '{}' requires the unknown pickaxe '{}'",
+                        pickaxe.id, requires
+                    ));
+                }
+            }
+        }
+
+        for (index, ability) in catalog.abilities.iter().enumerate() {
+            if catalog.abilities[..index]
+                .iter()
+                .any(|other| other.id == ability.id)
+            {
+                return Err(format!("duplicated ability id '{}'", ability.id));
+            }
+        }
+
+        for (index, auto_miner) in catalog.auto_miners.iter().enumerate() {
+            if catalog.auto_miners[..index]
+                .iter()
+                .any(|other| other.id == auto_miner.id)
+            {
+                return Err(format!("duplicated auto-miner id '{}'", auto_miner.id));
+            }
+            if auto_miner.interval <= 0.0 {
+                return Err(format!(
+                    "auto-miner '{}' needs a positive interval",
+                    auto_miner.id
+                ));
+            }
+        }
+
+        if catalog.repair.gold_per_point < 0.0 {
+            return Err(String::from("repairs can't have a negative gold_per_point"));
+        }
+
+        // The offline time is clamped to it, which panics on a negative or NaN limit
+        if catalog.offline.max_hours.is_nan() || catalog.offline.max_hours < 0.0 {
+            return Err(String::from("offline max_hours can't be negative"));
+        }
+
+        if catalog.ladders.bundle == 0 {
+            return Err(String::from("ladders need a bundle of at least one"));
+        }
+
+        for (index, upgrade) in catalog.storage.iter().enumerate() {
+            if catalog.storage[..index]
+                .iter()
+                .any(|other| other.id == upgrade.id)
+            {
+                return Err(format!("duplicated storage upgrade id '{}'", upgrade.id));
+            }
+            if let Some(requires) = &upgrade.requires {
+                if catalog.find_storage_upgrade(requires).is_none() {
+                    return Err(format!(
+                        "storage upgrade '{}' requires the unknown storage upgrade '{}'",
+                        upgrade.id, requires
+                    ));
+                }
+            }
+            if let StorageEffect::Compression { weight_multiplier } = upgrade.effect {
+                if weight_multiplier <= 0.0 {
+                    return Err(format!(
+                        "storage upgrade '{}' needs a positive weight_multiplier",
+                        upgrade.id
+                    ));
+                }
+            }
+        }
+
+        if let Some(prestige) = &catalog.prestige {
+            if prestige.score_per_rune <= 0.0 {
+                return Err(String::from("prestige needs a positive score_per_rune"));
+            }
+            for (index, upgrade) in prestige.upgrades.iter().enumerate() {
+                if prestige.upgrades[..index]
+                    .iter()
+                    .any(|other| other.id == upgrade.id)
+                {
+                    return Err(format!("duplicated prestige upgrade id '{}'", upgrade.id));
+                }
+            }
+        }
+
+        Ok(catalog)
+    }
+
+    // The first pickaxe in the file is the one every run starts with
+    pub fn starting_pickaxe(&self) -> PickaxeId {
+        PickaxeId(0)
+    }
+
+    pub fn pickaxe(&self, id: PickaxeId) -> &PickaxeDef {
+        &self.pickaxes[id.0]
+    }
+
+    pub fn find_pickaxe(&self, id: &str) -> Option<PickaxeId> {
+        self.pickaxes
+            .iter()
+            .position(|pickaxe| pickaxe.id == id)
+            .map(PickaxeId)
+    }
+
+    pub fn pickaxes(&self) -> impl Iterator<Item = (PickaxeId, &PickaxeDef)> {
+        self.pickaxes
+            .iter()
+            .enumerate()
+            .map(|(index, pickaxe)| (PickaxeId(index), pickaxe))
+    }
+
+    pub fn ability(&self, id: AbilityId) -> &AbilityDef {
+        &self.abilities[id.0]
+    }
+
+    pub fn find_ability(&self, id: &str) -> Option<AbilityId> {
+        self.abilities
+            .iter()
+            .position(|ability| ability.id == id)
+            .map(AbilityId)
+    }
+
+    pub fn abilities(&self) -> impl Iterator<Item = (AbilityId, &AbilityDef)> {
+        self.abilities
+            .iter()
+            .enumerate()
+            .map(|(index, ability)| (AbilityId(index), ability))
+    }
+
+    pub fn auto_miner(&self, id: AutoMinerId) -> &AutoMinerDef {
+        &self.auto_miners[id.0]
+    }
+
+    pub fn find_auto_miner(&self, id: &str) -> Option<AutoMinerId> {
+        self.auto_miners
+            .iter()
+            .position(|auto_miner| auto_miner.id == id)
+            .map(AutoMinerId)
+    }
+
+    pub fn auto_miners(&self) -> impl Iterator<Item = (AutoMinerId, &AutoMinerDef)> {
+        self.auto_miners
+            .iter()
+            .enumerate()
+            .map(|(index, auto_miner)| (AutoMinerId(index), auto_miner))
+    }
+
+    pub fn storage_upgrade(&self, id: StorageUpgradeId) -> &StorageUpgradeDef {
+        &self.storage[id.0]
+    }
+
+    pub fn find_storage_upgrade(&self, id: &str) -> Option<StorageUpgradeId> {
+        self.storage
+            .iter()
+            .position(|upgrade| upgrade.id == id)
+            .map(StorageUpgradeId)
+    }
+
+    pub fn storage_upgrades(&self) -> impl Iterator<Item = (StorageUpgradeId, &StorageUpgradeDef)> {
+        self.storage
+            .iter()
+            .enumerate()
+            .map(|(index, upgrade)| (StorageUpgradeId(index), upgrade))
+    }
+
+    // Why an ability can't be bought right now, or None if it can
+    pub fn ability_blocker(&self, owned: bool, wanted: AbilityId, gold: i32) -> Option<String> {
+        if owned {
+            return Some(String::from("Owned"));
+        }
+        if gold < self.ability(wanted).price {
+            return Some(String::from("Not enough gold"));
+        }
+        None
+    }
+
+    // Why a storage upgrade can't be bought right now, or None if it can
+    pub fn storage_blocker(
+        &self,
+        owned: &[bool],
+        wanted: StorageUpgradeId,
+        gold: i32,
+    ) -> Option<String> {
+        if let Some(reason) = self.storage_unlock_blocker(owned, wanted) {
+            return Some(reason);
+        }
+        if gold < self.storage_upgrade(wanted).price {
+            return Some(String::from("Not enough gold"));
+        }
+        None
+    }
+
+    // Same as `storage_blocker` without the price, crafting doesn't cost gold
+    pub fn storage_unlock_blocker(
+        &self,
+        owned: &[bool],
+        wanted: StorageUpgradeId,
+    ) -> Option<String> {
+        if owned[wanted.0] {
+            return Some(String::from("Owned"));
+        }
+
+        let required = self
+            .storage_upgrade(wanted)
+            .requires
+            .as_ref()
+            .and_then(|requires| self.find_storage_upgrade(requires));
+        if let Some(required) = required {
+            if !owned[required.0] {
+                return Some(format!("Needs {}", self.storage_upgrade(required).name));
+            }
+        }
+
+        None
+    }
+
+    // Why a pickaxe can't be bought right now, or None if it can
+    pub fn pickaxe_blocker(
+        &self,
+        owned: PickaxeId,
+        wanted: PickaxeId,
+        gold: i32,
+    ) -> Option<String> {
+        if let Some(reason) = self.pickaxe_unlock_blocker(owned, wanted) {
+            return Some(reason);
+        }
+        if gold < self.pickaxe(wanted).price {
+            return Some(String::from("Not enough gold"));
+        }
+        None
+    }
+
+    // Same as `pickaxe_blocker` without the price, crafting doesn't cost gold
+    pub fn pickaxe_unlock_blocker(&self, owned: PickaxeId, wanted: PickaxeId) -> Option<String> {
+        let owned = self.pickaxe(owned);
+        let wanted = self.pickaxe(wanted);
+
+        // Never allow going back to a lower (or the same) tier
+        if wanted.tier == owned.tier {
+            return Some(String::from("Owned"));
+        }
+        if wanted.tier < owned.tier {
+            return Some(String::from("You have a better one"));
+        }
+
+        let required = wanted
+            .requires
+            .as_ref()
+            .and_then(|requires| self.find_pickaxe(requires));
+        if let Some(required) = required {
+            let required = self.pickaxe(required);
+            if owned.tier < required.tier {
+                return Some(format!("Needs {}", required.name));
+            }
+        }
+
+        None
+    }
+}
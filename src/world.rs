@@ -0,0 +1,266 @@
+use std::collections::{BTreeMap, BTreeSet};
+
+use macroquad::prelude::*;
+
+use crate::{
+    block::Block,
+    block_registry::{BlockDef, BlockId, BlockRegistry},
+    shape::Shape,
+    simulation::BLOCK_SIZE,
+};
+
+pub const CHUNK_ROWS: i64 = 16;
+
+// Tile coordinates (column, row), row 0 is the surface line so the first row of blocks is row 1
+pub type Tile = (i32, i64);
+
+// A band of CHUNK_ROWS rows across the whole width of the world
+struct Chunk {
+    tiles: Vec<Option<Block>>, // Row by row, None means dug out or part of a cave
+}
+
+// What's kept of a chunk once it leaves the screen, the shapes can be worked out again from the tile
+pub struct CompactChunk {
+    pub blocks: Vec<u16>,         // BlockId + 1 of every tile, 0 is empty
+    pub damaged: Vec<(u32, i32)>, // Tile index and hp of the blocks that aren't at full hp anymore
+}
+
+// Blocks by tile instead of one long list, so looking up the tiles around something doesn't mean
+// going through every block in the world
+pub struct World {
+    width: i32,                   // Columns
+    top: f32,                     // World y of row 0
+    chunks: BTreeMap<i64, Chunk>, // By chunk index, BTreeMaps so going through them is always in the same order
+    evicted: BTreeMap<i64, CompactChunk>,
+    ladders: BTreeSet<Tile>, // There are only ever a few, so they aren't split in chunks
+}
+
+impl World {
+    pub fn new(width: i32, top: f32) -> Self {
+        Self {
+            width,
+            top,
+            chunks: BTreeMap::new(),
+            evicted: BTreeMap::new(),
+            ladders: BTreeSet::new(),
+        }
+    }
+
+    pub fn get(&self, tile: Tile) -> Option<&Block> {
+        let index = self.index(tile)?;
+        self.chunks.get(&Self::chunk_of(tile.1))?.tiles[index].as_ref()
+    }
+
+    pub fn get_mut(&mut self, tile: Tile) -> Option<&mut Block> {
+        let index = self.index(tile)?;
+        self.chunks.get_mut(&Self::chunk_of(tile.1))?.tiles[index].as_mut()
+    }
+
+    pub fn is_solid(&self, tile: Tile) -> bool {
+        self.get(tile).is_some()
+    }
+
+    // Puts a new block with full hp in the tile, replacing whatever was there
+    pub fn place(&mut self, tile: Tile, block_id: BlockId, def: &BlockDef) -> Option<&mut Block> {
+        let index = self.index(tile)?;
+        let shape = self.tile_shape(tile);
+        let width = self.width;
+        let chunk = self
+            .chunks
+            .entry(Self::chunk_of(tile.1))
+            .or_insert_with(|| Chunk::new(width));
+        chunk.tiles[index] = Some(Block::new(shape, block_id, def));
+        chunk.tiles[index].as_mut()
+    }
+
+    pub fn remove(&mut self, tile: Tile) -> Option<Block> {
+        let index = self.index(tile)?;
+        self.chunks.get_mut(&Self::chunk_of(tile.1))?.tiles[index].take()
+    }
+
+    pub fn has_ladder(&self, tile: Tile) -> bool {
+        self.ladders.contains(&tile)
+    }
+
+    // Ladders go in empty tiles inside the world, returns false if one can't go there
+    pub fn place_ladder(&mut self, tile: Tile) -> bool {
+        if self.index(tile).is_none() || self.is_solid(tile) {
+            return false;
+        }
+        self.ladders.insert(tile)
+    }
+
+    pub fn ladders(&self) -> impl Iterator<Item = Tile> + '_ {
+        self.ladders.iter().copied()
+    }
+
+    // Tile of a point in world coordinates
+    pub fn tile_at(&self, point: Vec2) -> Tile {
+        (
+            (point.x / BLOCK_SIZE).floor() as i32,
+            ((point.y - self.top) / BLOCK_SIZE).floor() as i64,
+        )
+    }
+
+    // Every tile inside the world that touches the area between the two corners, row by row
+    pub fn tiles_between(&self, min: Vec2, max: Vec2) -> impl Iterator<Item = Tile> {
+        let (first_column, first_row) = self.tile_at(min);
+        let (last_column, last_row) = self.tile_at(max);
+        let columns = first_column.max(0)..=last_column.min(self.width - 1);
+        (first_row..=last_row).flat_map(move |row| columns.clone().map(move |column| (column, row)))
+    }
+
+    // Same as `tiles_between` but only the tiles with a block in them
+    pub fn blocks_between(&self, min: Vec2, max: Vec2) -> impl Iterator<Item = (Tile, &Block)> {
+        self.tiles_between(min, max)
+            .filter_map(|tile| self.get(tile).map(|block| (tile, block)))
+    }
+
+    // Blocks of the chunks overlapping the rows between the two world y, the other chunks are skipped as a whole
+    pub fn visible_blocks(&self, top_y: f32, bottom_y: f32) -> impl Iterator<Item = &Block> {
+        let first_chunk = Self::chunk_of(self.tile_at(vec2(0.0, top_y)).1);
+        let last_chunk = Self::chunk_of(self.tile_at(vec2(0.0, bottom_y)).1);
+        self.chunks
+            .range(first_chunk..=last_chunk)
+            .flat_map(|(_, chunk)| chunk.tiles.iter().flatten())
+    }
+
+    // Every loaded block, top to bottom
+    pub fn blocks(&self) -> impl Iterator<Item = (Tile, &Block)> {
+        self.chunks.iter().flat_map(move |(&chunk, tiles)| {
+            tiles
+                .tiles
+                .iter()
+                .enumerate()
+                .filter_map(move |(index, block)| {
+                    block
+                        .as_ref()
+                        .map(|block| (self.tile_of(chunk, index), block))
+                })
+        })
+    }
+
+    // Chunks that are completely above `row` get packed away
+    pub fn evict_above(&mut self, row: i64, registry: &BlockRegistry) {
+        let last_chunk = Self::chunk_of(row) - 1;
+        let evicted: Vec<i64> = self
+            .chunks
+            .range(..=last_chunk)
+            .map(|(&chunk, _)| chunk)
+            .collect();
+        for chunk in evicted {
+            if let Some(tiles) = self.chunks.remove(&chunk) {
+                self.evicted.insert(chunk, tiles.compact(registry));
+            }
+        }
+    }
+
+    // Unpacks the evicted chunks overlapping the rows, exactly as they were left
+    pub fn restore_between(&mut self, first_row: i64, last_row: i64, registry: &BlockRegistry) {
+        let restored: Vec<i64> = self
+            .evicted
+            .range(Self::chunk_of(first_row)..=Self::chunk_of(last_row))
+            .map(|(&chunk, _)| chunk)
+            .collect();
+        for chunk in restored {
+            let Some(compact) = self.evicted.remove(&chunk) else {
+                continue;
+            };
+
+            let mut tiles = Chunk::new(self.width);
+            for (index, &block) in compact.blocks.iter().enumerate() {
+                if block == 0 {
+                    continue;
+                }
+                let block_id = BlockId(block as usize - 1);
+                let shape = self.tile_shape(self.tile_of(chunk, index));
+                tiles.tiles[index] = Some(Block::new(shape, block_id, registry.get(block_id)));
+            }
+            for &(index, hp) in &compact.damaged {
+                if let Some(block) = &mut tiles.tiles[index as usize] {
+                    block.hp = hp;
+                }
+            }
+            self.chunks.insert(chunk, tiles);
+        }
+    }
+
+    // Packed chunks by chunk index, top to bottom
+    pub fn evicted(&self) -> impl Iterator<Item = (i64, &CompactChunk)> {
+        self.evicted
+            .iter()
+            .map(|(&chunk, compact)| (chunk, compact))
+    }
+
+    pub fn loaded_chunks(&self) -> usize {
+        self.chunks.len()
+    }
+
+    pub fn evicted_chunks(&self) -> usize {
+        self.evicted.len()
+    }
+
+    //
+    // HELPERS
+    //
+
+    fn chunk_of(row: i64) -> i64 {
+        row.div_euclid(CHUNK_ROWS)
+    }
+
+    // Index of the tile inside its chunk, None for tiles outside of the world on the sides
+    fn index(&self, (column, row): Tile) -> Option<usize> {
+        if !(0..self.width).contains(&column) {
+            return None;
+        }
+        Some((row.rem_euclid(CHUNK_ROWS) * self.width as i64 + column as i64) as usize)
+    }
+
+    fn tile_of(&self, chunk: i64, index: usize) -> Tile {
+        let index = index as i64;
+        (
+            (index % self.width as i64) as i32,
+            chunk * CHUNK_ROWS + index / self.width as i64,
+        )
+    }
+
+    fn tile_shape(&self, (column, row): Tile) -> Shape {
+        Shape {
+            x: column as f32 * BLOCK_SIZE,
+            y: self.top + row as f32 * BLOCK_SIZE,
+            size: Vec2::splat(BLOCK_SIZE),
+            color: RED,
+        }
+    }
+}
+
+impl Chunk {
+    fn new(width: i32) -> Self {
+        Self {
+            tiles: (0..width as i64 * CHUNK_ROWS).map(|_| None).collect(),
+        }
+    }
+
+    fn compact(&self, registry: &BlockRegistry) -> CompactChunk {
+        let blocks = self
+            .tiles
+            .iter()
+            .map(|block| {
+                block
+                    .as_ref()
+                    .map_or(0, |block| block.block_id.0 as u16 + 1)
+            })
+            .collect();
+        let damaged = self
+            .tiles
+            .iter()
+            .enumerate()
+            .filter_map(|(index, block)| {
+                let block = block.as_ref()?;
+                let is_damaged = block.hp < registry.get(block.block_id).base_hp;
+                is_damaged.then_some((index as u32, block.hp))
+            })
+            .collect();
+        CompactChunk { blocks, damaged }
+    }
+}